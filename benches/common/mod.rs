@@ -0,0 +1,27 @@
+use kindle_formats::krds::*;
+use linked_hash_map::LinkedHashMap;
+
+const NOTES: usize = 10_000;
+
+/// Reader data file with `NOTES` handwritten notes.
+pub fn large_reader_data() -> ReaderDataFile {
+    let notes = (0..NOTES)
+        .map(|i| {
+            let pos = format!("AdgGAAAAAAAA:{}", i);
+            Note::Handwritten(AnnotationData(
+                pos.clone(),
+                pos,
+                1693039707755 + i as i64,
+                1693039707755 + i as i64,
+                "0\u{fffc}0".to_string(),
+                Some(format!("cRgtuIx_zS-m4geT-{:07}", i)),
+            ))
+        })
+        .collect();
+    let mut annotations = LinkedHashMap::new();
+    annotations.insert(NoteType::Handwritten, IntervalTree(notes));
+    ReaderDataFile {
+        annotation_cache: Some(annotations),
+        ..Default::default()
+    }
+}
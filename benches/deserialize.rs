@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use kindle_formats::krds::ReaderDataFile;
+use serde_krds::{from_bytes, to_bytes};
+
+mod common;
+
+use common::large_reader_data;
+
+const PDFANNOT_YJR: &[u8] = include_bytes!("../test_files/pdfannot.yjr");
+
+fn deserialize(c: &mut Criterion) {
+    let large = to_bytes(&large_reader_data()).unwrap();
+
+    let mut group = c.benchmark_group("deserialize");
+    for (name, bytes) in [("pdfannot.yjr", PDFANNOT_YJR), ("large", &large[..])] {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| from_bytes::<ReaderDataFile>(bytes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, deserialize);
+criterion_main!(benches);
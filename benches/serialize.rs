@@ -0,0 +1,22 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serde_krds::{to_bytes, to_bytes_with_capacity};
+
+mod common;
+
+use common::large_reader_data;
+
+fn serialize(c: &mut Criterion) {
+    let data = large_reader_data();
+    let len = to_bytes(&data).unwrap().len();
+
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.bench_function("to_bytes", |b| b.iter(|| to_bytes(&data).unwrap()));
+    group.bench_function("to_bytes_with_capacity", |b| {
+        b.iter(|| to_bytes_with_capacity(&data, len).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+//! Arbitrary input must only ever produce an error, never a panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_krds::KrdsValue;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = KrdsValue::from_document(data);
+});
@@ -1,5 +1,12 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::io::Read;
+#[cfg(feature = "std")]
+use std::path::Path;
 
+use serde::de::DeserializeOwned;
 use serde::de::{
     self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
     Visitor,
@@ -7,6 +14,7 @@ use serde::de::{
 use serde::Deserialize;
 
 use crate::error::{Error, Result};
+use crate::event::KrdsScalar;
 
 use crate::DataType;
 
@@ -14,11 +22,123 @@ use crate::DataType;
 pub struct Deserializer<'de> {
     input: &'de [u8],
     counter: usize,
+    deny_unknown_fields: bool,
+    max_container_len: Option<usize>,
+    field_names: FieldNameMap,
+    /// Levels of nesting left before [`RECURSION_LIMIT`] is reached.
+    remaining_depth: u8,
 }
 
+/// Deepest nesting of values the deserializer follows (the same as
+/// serde_json), deeper input is rejected instead of overflowing the
+/// stack.
+const RECURSION_LIMIT: u8 = 128;
+
+/// Names of struct fields in the file mapped to the names the target
+/// struct expects, see [`Deserializer::set_field_name_map`].
+pub type FieldNameMap = BTreeMap<String, String>;
+
 impl<'de> Deserializer<'de> {
     pub fn from_bytes(input: &'de [u8]) -> Self {
-        Deserializer { input, counter: 0 }
+        Deserializer {
+            input,
+            counter: 0,
+            deny_unknown_fields: false,
+            max_container_len: None,
+            field_names: FieldNameMap::new(),
+            remaining_depth: RECURSION_LIMIT,
+        }
+    }
+
+    /// Create a deserializer for a whole file, checking and skipping
+    /// the magic bytes. The magic is all that is required, a file
+    /// holding only a `None` has no values after it and the smallest
+    /// value (a `Boolean` or `Byte`) is two bytes long.
+    pub(crate) fn with_magic(b: &'de [u8]) -> Result<Self> {
+        let mut deserializer = Deserializer::from_bytes(b);
+        let version = deserializer.parse_magic()?;
+
+        if version != crate::VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        Ok(deserializer)
+    }
+
+    /// Check and skip the signature, returning the format version that
+    /// follows it without checking that it is supported.
+    fn parse_magic(&mut self) -> Result<u64> {
+        if self.input.len() < crate::MAGIC.len() {
+            return Err(Error::Eof {
+                pos: self.counter + self.input.len(),
+            });
+        }
+
+        if &self.get_array()? != crate::SIGNATURE {
+            return Err(Error::BadMagic);
+        }
+
+        Ok(u64::from_be_bytes(self.get_array()?))
+    }
+
+    /// Check that all of the input has been consumed.
+    pub(crate) fn end(&self) -> Result<()> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes {
+                pos: self.counter,
+                remaining: self.input.len(),
+            })
+        }
+    }
+
+    /// When enabled, a struct field that the target struct does not
+    /// have results in an [`Error::UnknownField`] instead of being
+    /// ignored.
+    pub fn set_deny_unknown_fields(&mut self, deny: bool) {
+        self.deny_unknown_fields = deny;
+    }
+
+    /// Cap the declared length of any sequence, map or struct, longer
+    /// ones result in an [`Error::LengthLimitExceeded`]. By default
+    /// only struct lengths are limited, by the size of the input.
+    /// `None` elements take no space, so a short untrusted file may
+    /// still declare a sequence of billions of them.
+    pub fn set_max_container_len(&mut self, max: usize) {
+        self.max_container_len = Some(max);
+    }
+
+    /// Rename struct fields as they are read, e.g. to try out a guess
+    /// at what an unknown field holds without changing the `rename`
+    /// attributes of the target struct. Names that are not in the map
+    /// are left as they are.
+    pub fn set_field_name_map(&mut self, map: FieldNameMap) {
+        self.field_names = map;
+    }
+
+    /// Deserialize the next `len` bytes as a separate document (e.g. a
+    /// KRDS blob embedded with a byte length), starting with the KRDS
+    /// magic if `magic` is set. The whole blob must be consumed, error
+    /// positions are relative to its start. Settings of this
+    /// deserializer apply to the blob too.
+    pub fn read_nested<T>(&mut self, len: usize, magic: bool) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let bytes = self.get_slice(len)?;
+        let mut nested = if magic {
+            Deserializer::with_magic(bytes)?
+        } else {
+            Deserializer::from_bytes(bytes)
+        };
+        nested.deny_unknown_fields = self.deny_unknown_fields;
+        nested.max_container_len = self.max_container_len;
+        nested.field_names = self.field_names.clone();
+        nested.remaining_depth = self.remaining_depth;
+        let t = T::deserialize(&mut nested)?;
+        nested.end()?;
+        Ok(t)
     }
 }
 
@@ -26,25 +146,229 @@ pub fn from_bytes<'a, T>(b: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    if b.len() < crate::MAGIC.len() + 5 {
-        return Err(Error::Eof);
+    let (t, rest) = from_bytes_partial(b)?;
+    if rest.is_empty() {
+        Ok(t)
+    } else {
+        Err(Error::TrailingBytes {
+            pos: b.len() - rest.len(),
+            remaining: rest.len(),
+        })
     }
+}
 
-    let magic = &b[..crate::MAGIC.len()];
+/// Deserialize a value written by [`to_bytes_raw`](crate::to_bytes_raw),
+/// i.e. without the KRDS magic. The whole input must be consumed.
+pub fn from_bytes_raw<'a, T>(b: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(b);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
 
-    if magic != crate::MAGIC {
-        return Err(Error::BadMagic);
-    }
+/// Deserialize all the values of a KRDS file as the elements of a
+/// sequence, e.g. into a `Vec<KrdsValue>` to inspect a file without a
+/// matching struct (see [`KrdsValue::from_document`].)
+///
+/// [`KrdsValue::from_document`]: crate::KrdsValue::from_document
+pub fn from_document<'a, T>(b: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::with_magic(b)?;
+    T::deserialize(Document::new(&mut deserializer))
+}
 
-    let mut deserializer = Deserializer::from_bytes(&b[crate::MAGIC.len()..]);
+/// Like [`from_bytes`] but does not require the whole input to be
+/// consumed, the unread bytes are returned along with the value (e.g.
+/// for files storing several records back to back.)
+pub fn from_bytes_partial<'a, T>(b: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::with_magic(b)?;
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.input))
+}
 
-    deserializer.counter = crate::MAGIC.len();
+/// Like [`from_bytes`] but the magic may be preceded by up to `limit`
+/// stray bytes (e.g. left by a broken export tool), which are skipped.
+/// Returns the value and the number of bytes skipped. Error positions
+/// are relative to the start of the magic.
+pub fn from_bytes_scan<'a, T>(b: &'a [u8], limit: usize) -> Result<(T, usize)>
+where
+    T: Deserialize<'a>,
+{
+    let skipped = b
+        .windows(crate::MAGIC.len())
+        .take(limit.saturating_add(1))
+        .position(|window| window == crate::MAGIC)
+        .ok_or(Error::BadMagic)?;
+    Ok((from_bytes(&b[skipped..])?, skipped))
+}
 
+/// Like [`from_bytes`] but fails with [`Error::UnknownField`] if the
+/// file contains a struct field that `T` does not know about.
+pub fn from_bytes_strict<'a, T>(b: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::with_magic(b)?;
+    deserializer.set_deny_unknown_fields(true);
     let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingBytes)
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize a value from any reader. The reader is read to the
+/// end into an owned buffer before parsing, so this allocates a copy
+/// of the whole file. Use [`from_bytes`] if the data is already in
+/// memory.
+#[cfg(feature = "std")]
+pub fn from_reader<R, T>(mut reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    from_bytes(&buf)
+}
+
+/// Read and deserialize a KRDS file at `path`.
+#[cfg(feature = "std")]
+pub fn from_file<P, T>(path: P) -> Result<T>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    from_bytes(&std::fs::read(path)?)
+}
+
+/// Deserializer owning its input, for keeping a file around and
+/// deserializing it later without borrowing from a temporary buffer.
+/// Values cannot borrow from it, so only [`DeserializeOwned`] types
+/// are supported.
+#[derive(Clone, Debug)]
+pub struct OwnedDeserializer {
+    input: Vec<u8>,
+}
+
+impl OwnedDeserializer {
+    /// Take ownership of a whole KRDS file, it is not checked until
+    /// [`deserialize`](Self::deserialize) is called.
+    pub fn from_vec(input: Vec<u8>) -> Self {
+        OwnedDeserializer { input }
+    }
+
+    /// Deserialize the file, as with [`from_bytes`]. Can be called any
+    /// number of times.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        from_bytes(&self.input)
+    }
+
+    /// Get back the file contents.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.input
+    }
+}
+
+/// Kind of KRDS file, as guessed by [`sniff_file_type`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FileKind {
+    /// Reader data (`.yjr`, `.azw3r`, ...) with annotations and
+    /// reader settings.
+    ReaderData,
+    /// Timer data (`.yjf`, `.azw3f`, ...) with reading time and page
+    /// history.
+    TimerData,
+    Unknown,
+}
+
+/// Top-level field names only found in reader data files.
+const READER_DATA_FIELDS: &[&str] = &[
+    "annotation.cache.object",
+    "apnx.key",
+    "booklaunchedbefore",
+    "font.prefs",
+    "language.store",
+    "next.in.series.info.data",
+    "reader.state.preferences",
+    "sync_lpr",
+];
+
+/// Top-level field names only found in timer data files.
+const TIMER_DATA_FIELDS: &[&str] = &[
+    "book.info.store",
+    "fpr",
+    "lpr",
+    "page.history.store",
+    "timer.model",
+    "whisperstore.migration.status",
+];
+
+/// Guess the kind of a KRDS file from its top-level field names, so it
+/// can be deserialized without knowing the file extension. The first
+/// field that only appears in one kind of file decides.
+pub fn sniff_file_type(b: &[u8]) -> Result<FileKind> {
+    for name in read_header(b)?.fields {
+        if READER_DATA_FIELDS.contains(&name.as_str()) {
+            return Ok(FileKind::ReaderData);
+        }
+        if TIMER_DATA_FIELDS.contains(&name.as_str()) {
+            return Ok(FileKind::TimerData);
+        }
+    }
+
+    Ok(FileKind::Unknown)
+}
+
+/// Format version and top-level fields of a KRDS file, see
+/// [`read_header`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub version: u64,
+    /// Names of the top-level fields, in file order.
+    pub fields: Vec<String>,
+}
+
+/// Read the version and top-level field names of a KRDS file. Field
+/// values are skipped over without being deserialized. Unlike
+/// [`from_bytes`] this does not reject versions other than the known
+/// one, the fields are read as if the layout had not changed.
+pub fn read_header(b: &[u8]) -> Result<Header> {
+    let mut deserializer = Deserializer::from_bytes(b);
+    let version = deserializer.parse_magic()?;
+    let count = deserializer.parse_field_count()?;
+
+    let mut fields = Vec::with_capacity(count);
+    for _ in 0..count {
+        deserializer.parse_type(DataType::FieldBegin)?;
+        fields.push(deserializer.parse_string()?.to_string());
+        while deserializer.peek_next_datatype()? != DataType::FieldEnd {
+            deserializer.skip_value()?;
+        }
+        deserializer.next_byte()?;
+    }
+
+    Ok(Header { version, fields })
+}
+
+/// Attach the offset of a data type byte to an [`Error::UnknownType`]
+/// produced by the `TryFrom` conversion.
+fn with_pos(error: Error, pos: usize) -> Error {
+    match error {
+        Error::UnknownType { value, .. } => Error::UnknownType {
+            value,
+            pos: Some(pos),
+        },
+        e => e,
     }
 }
 
@@ -56,50 +380,185 @@ impl<'de> Deserializer<'de> {
     }
 
     fn peek_byte(&mut self) -> Result<u8> {
-        let byte = self.input.bytes().next().ok_or(Error::Eof)??;
-        Ok(byte)
+        self.input
+            .first()
+            .copied()
+            .ok_or(Error::Eof { pos: self.counter })
     }
 
-    fn next_byte(&mut self) -> Result<u8> {
+    pub(crate) fn next_byte(&mut self) -> Result<u8> {
         let byte = self.peek_byte()?;
         self.consume_unchecked(1);
         Ok(byte)
     }
 
-    fn get_array<const N: usize>(&mut self) -> Result<[u8; N]> {
-        if self.input.len() < N {
-            return Err(Error::Eof);
-        }
-        let buf: [u8; N] = *&self.input[0..N].try_into().unwrap();
+    pub(crate) fn get_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let &buf = self.input.first_chunk().ok_or(Error::UnexpectedEof {
+            needed: N,
+            got: self.input.len(),
+            pos: self.counter,
+        })?;
         self.consume_unchecked(N);
         Ok(buf)
     }
 
-    fn get_slice(&mut self, count: usize) -> Result<&[u8]> {
-        if self.input.len() < count {
-            return Err(Error::Eof);
-        }
-        let slice = &self.input[..count];
+    fn get_slice(&mut self, count: usize) -> Result<&'de [u8]> {
+        let input = self.input;
+        let slice = input.get(..count).ok_or(Error::UnexpectedEof {
+            needed: count,
+            got: input.len(),
+            pos: self.counter,
+        })?;
         self.consume_unchecked(count);
         Ok(slice)
     }
 
-    fn parse_string(&mut self) -> Result<&str> {
-        let mut value = "";
-        if self.next_byte()? != 1 {
-            let length = u16::from_be_bytes(self.get_array()?) as usize;
-            value = std::str::from_utf8(self.get_slice(length)?).unwrap();
+    /// Contents of a string, see [`StringLen`](crate::StringLen).
+    fn parse_string_bytes(&mut self) -> Result<&'de [u8]> {
+        if self.next_byte()? == crate::EMPTY_STRING {
+            return Ok(&[]);
         }
-        Ok(value)
+        let length = crate::StringLen::from_be_bytes(self.get_array()?) as usize;
+        self.get_slice(length)
+    }
+
+    /// Strings are borrowed from the input, so `&'de str` fields do not
+    /// allocate.
+    pub(crate) fn parse_string(&mut self) -> Result<&'de str> {
+        let bytes = self.parse_string_bytes()?;
+        core::str::from_utf8(bytes).map_err(|_| Error::InvalidUtf8 {
+            pos: self.counter - bytes.len(),
+            bytes: bytes.to_vec(),
+        })
     }
 
-    fn parse_i32(&mut self) -> Result<i32> {
+    pub(crate) fn parse_i32(&mut self) -> Result<i32> {
         let value = i32::from_be_bytes(self.get_array()?);
         Ok(value)
     }
 
-    fn next_datatype(&mut self) -> Result<DataType> {
-        self.next_byte()?.try_into()
+    /// Length prefix of a sequence, map or struct. Elements may take no
+    /// bytes at all (`None` is not written), so the length is not
+    /// checked against the rest of the input, see
+    /// [`parse_field_count`](Self::parse_field_count).
+    fn parse_length(&mut self) -> Result<usize> {
+        let pos = self.counter;
+        self.parse_type(DataType::Int)?;
+        let len = self.parse_i32()?;
+        if len < 0 {
+            return Err(Error::InvalidLength { len, pos });
+        }
+        if let Some(limit) = self.max_container_len.filter(|&limit| len as usize > limit) {
+            return Err(Error::LengthLimitExceeded {
+                len: len as usize,
+                limit,
+                pos,
+            });
+        }
+        Ok(len as usize)
+    }
+
+    /// Length prefix of a struct. Every field takes at least one byte
+    /// (its `FieldBegin`), so a length longer than the rest of the input
+    /// can only come from a corrupt file.
+    fn parse_field_count(&mut self) -> Result<usize> {
+        let pos = self.counter;
+        let len = self.parse_length()?;
+        if len > self.input.len() {
+            return Err(Error::InvalidLength {
+                len: len as i32,
+                pos,
+            });
+        }
+        Ok(len)
+    }
+
+    /// Move past the next value (and everything in it for a
+    /// `FieldBegin`) without building anything. Nested fields are
+    /// counted rather than recursed into, so no amount of nesting can
+    /// overflow the stack.
+    fn skip_value(&mut self) -> Result<()> {
+        let mut depth = 0_usize;
+        loop {
+            let pos = self.counter;
+            let datatype = self.next_datatype()?;
+            match (datatype.byte_width(), datatype) {
+                (Some(width), _) => self.consume(width)?,
+                (None, DataType::String) => self.skip_string()?,
+                (None, DataType::FieldBegin) => {
+                    self.skip_string()?;
+                    depth += 1;
+                }
+                (None, DataType::FieldEnd) if depth > 0 => depth -= 1,
+                _ => {
+                    return Err(Error::Unexpected {
+                        want: None,
+                        got: datatype,
+                        pos,
+                    })
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Run `f` on a value one level deeper, see [`RECURSION_LIMIT`].
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.remaining_depth == 0 {
+            return Err(Error::RecursionLimitExceeded { pos: self.counter });
+        }
+        self.remaining_depth -= 1;
+        let result = f(self);
+        self.remaining_depth += 1;
+        result
+    }
+
+    fn skip_string(&mut self) -> Result<()> {
+        self.parse_string_bytes().map(|_| ())
+    }
+
+    /// Read the value following a data type byte that has already
+    /// been consumed, so callers that dispatch on the type do not read
+    /// it twice. `FieldBegin` and `FieldEnd` have no value of their
+    /// own.
+    pub(crate) fn parse_scalar(&mut self, datatype: DataType) -> Result<KrdsScalar<'de>> {
+        Ok(match datatype {
+            DataType::Boolean => KrdsScalar::Bool(self.next_byte()? != 0),
+            DataType::Int => KrdsScalar::Int(self.parse_i32()?),
+            DataType::Long => KrdsScalar::Long(i64::from_be_bytes(self.get_array()?)),
+            DataType::String => KrdsScalar::String(self.parse_string()?),
+            DataType::Double => KrdsScalar::Double(f64::from_be_bytes(self.get_array()?)),
+            DataType::Short => KrdsScalar::Short(i16::from_be_bytes(self.get_array()?)),
+            DataType::Float => KrdsScalar::Float(f32::from_be_bytes(self.get_array()?)),
+            DataType::Byte => KrdsScalar::Byte(self.next_byte()? as i8),
+            DataType::Char => KrdsScalar::Char(self.parse_char()?),
+            DataType::FieldBegin | DataType::FieldEnd => {
+                return Err(Error::Unexpected {
+                    want: None,
+                    got: datatype,
+                    pos: self.counter - 1,
+                })
+            }
+        })
+    }
+
+    /// Chars are a single UTF-16 code unit, lone surrogates are not
+    /// valid chars.
+    pub(crate) fn parse_char(&mut self) -> Result<char> {
+        let pos = self.counter;
+        let unit = u16::from_be_bytes(self.get_array()?);
+        char::from_u32(unit as u32).ok_or(Error::BadValue { pos })
+    }
+
+    fn consume(&mut self, count: usize) -> Result<()> {
+        self.get_slice(count).map(|_| ())
+    }
+
+    pub(crate) fn next_datatype(&mut self) -> Result<DataType> {
+        let pos = self.counter;
+        self.next_byte()?.try_into().map_err(|e| with_pos(e, pos))
     }
 
     fn parse_type(&mut self, datatype: DataType) -> Result<()> {
@@ -115,41 +574,65 @@ impl<'de> Deserializer<'de> {
         }
     }
 
-    fn peek_next_datatype(&mut self) -> Result<DataType> {
-        self.peek_byte()?.try_into()
+    pub(crate) fn peek_next_datatype(&mut self) -> Result<DataType> {
+        let pos = self.counter;
+        self.peek_byte()?.try_into().map_err(|e| with_pos(e, pos))
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
+    /// Values are not self-describing (an `Int` may be a length
+    /// prefix), so this only ever produces scalars and a map with a
+    /// single entry (name to field values) for each
+    /// `FieldBegin`/`FieldEnd` pair. Like any other value this is only
+    /// the next one in the input, see [`from_document`] for all the
+    /// values in a document.
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        match self.peek_next_datatype()? {
-            DataType::Boolean => self.deserialize_bool(visitor),
-            DataType::Int => self.deserialize_i32(visitor),
-            DataType::Long => self.deserialize_i64(visitor),
-            DataType::String => self.deserialize_string(visitor),
-            DataType::Double => self.deserialize_f64(visitor),
-            DataType::Short => self.deserialize_i16(visitor),
-            DataType::Float => self.deserialize_f32(visitor),
-            DataType::Byte => self.deserialize_i8(visitor),
-            DataType::Char => self.deserialize_char(visitor),
-            _ => Err(Error::WontImplement),
+        match self.next_datatype()? {
+            DataType::FieldBegin => self.nested(|de| {
+                let value = visitor.visit_map(Object::new(de))?;
+                de.parse_type(DataType::FieldEnd)?;
+                Ok(value)
+            }),
+            datatype => match self.parse_scalar(datatype)? {
+                KrdsScalar::Bool(v) => visitor.visit_bool(v),
+                KrdsScalar::Int(v) => visitor.visit_i32(v),
+                KrdsScalar::Long(v) => visitor.visit_i64(v),
+                KrdsScalar::String(v) => visitor.visit_borrowed_str(v),
+                KrdsScalar::Double(v) => visitor.visit_f64(v),
+                KrdsScalar::Short(v) => visitor.visit_i16(v),
+                KrdsScalar::Float(v) => visitor.visit_f32(v),
+                KrdsScalar::Byte(v) => visitor.visit_i8(v),
+                KrdsScalar::Char(v) => visitor.visit_char(v),
+            },
         }
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    /// An `Int` of 0 or 1 is accepted as well, some producers write
+    /// booleans that way.
+    fn deserialize_bool<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.peek_next_datatype()? == DataType::Int {
+            self.next_byte()?;
+            let pos = self.counter;
+            return match self.parse_i32()? {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                _ => Err(Error::BadValue { pos }),
+            };
+        }
         self.parse_type(DataType::Boolean)?;
-        visitor.visit_bool(if self.next_byte()? == 0 { false } else { true })
+        visitor.visit_bool(self.next_byte()? != 0)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i8<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -157,7 +640,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i8(self.next_byte()? as i8)
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i16<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -165,15 +648,21 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i16(i16::from_be_bytes(self.get_array()?))
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    /// A `Short` is accepted and widened, some firmware versions store
+    /// note type keys this way.
+    fn deserialize_i32<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.peek_next_datatype()? == DataType::Short {
+            self.next_byte()?;
+            return visitor.visit_i32(i16::from_be_bytes(self.get_array()?) as i32);
+        }
         self.parse_type(DataType::Int)?;
         visitor.visit_i32(self.parse_i32()?)
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_i64<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -181,31 +670,48 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i64(i64::from_be_bytes(self.get_array()?))
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    /// A `Double` is accepted if it can be narrowed without losing
+    /// precision.
+    fn deserialize_f32<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.peek_next_datatype()? == DataType::Double {
+            self.next_byte()?;
+            let pos = self.counter;
+            let value = f64::from_be_bytes(self.get_array()?);
+            let narrowed = value as f32;
+            if narrowed as f64 != value && !value.is_nan() {
+                return Err(Error::BadValue { pos });
+            }
+            return visitor.visit_f32(narrowed);
+        }
         self.parse_type(DataType::Float)?;
         visitor.visit_f32(f32::from_be_bytes(self.get_array()?))
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    /// A `Float` is accepted and widened.
+    fn deserialize_f64<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if self.peek_next_datatype()? == DataType::Float {
+            self.next_byte()?;
+            return visitor.visit_f64(f32::from_be_bytes(self.get_array()?) as f64);
+        }
         self.parse_type(DataType::Double)?;
         visitor.visit_f64(f64::from_be_bytes(self.get_array()?))
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_char<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         self.parse_type(DataType::Char)?;
-        visitor.visit_char(self.next_byte()? as char)
+        visitor.visit_char(self.parse_char()?)
     }
 
-    fn deserialize_string<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_string<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
@@ -245,11 +751,29 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_u64(u64::from_be_bytes(self.get_array()?))
     }
 
+    fn deserialize_i128<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::UnsupportedType("i128"))
+    }
+
+    fn deserialize_u128<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::UnsupportedType("u128"))
+    }
+
+    /// Borrows from the input, so `Cow<'de, str>` fields marked with
+    /// `#[serde(borrow)]` can share the file's bytes instead of
+    /// allocating a copy of every repeated string.
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_str((self.parse_string())?)
+        self.parse_type(DataType::String)?;
+        visitor.visit_borrowed_str(self.parse_string()?)
     }
 
     fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
@@ -270,8 +794,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if self.peek_next_datatype()? == DataType::FieldEnd {
-            dbg!("me");
+        // An absent optional may also be the last value of the input.
+        if self.input.is_empty() || self.peek_next_datatype()? == DataType::FieldEnd {
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -284,26 +808,28 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         self.parse_type(DataType::FieldBegin)?;
         self.parse_string()?;
-        let value = visitor.visit_newtype_struct(&mut *self)?;
-        self.parse_type(DataType::FieldEnd)?;
-        Ok(value)
+        self.nested(|de| {
+            let value = visitor.visit_newtype_struct(&mut *de)?;
+            de.parse_type(DataType::FieldEnd)?;
+            Ok(value)
+        })
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.parse_type(DataType::Int)?;
-        let length = self.parse_i32()? as usize;
-        let value = visitor.visit_seq(LengthBased::new(self, length))?;
-        Ok(value)
+        let length = self.parse_length()?;
+        self.nested(|de| visitor.visit_seq(LengthBased::new(de, length)))
     }
 
+    /// Tuples and fixed size arrays have no length prefix, like tuple
+    /// structs.
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(LengthBased::new(self, len))
+        self.nested(|de| visitor.visit_seq(Terminated::new(de, Some(len))))
     }
 
     fn deserialize_tuple_struct<V>(
@@ -315,37 +841,37 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_seq(Terminated::new(self, Some(len)))?;
-        Ok(value)
+        self.nested(|de| visitor.visit_seq(Terminated::new(de, Some(len))))
     }
 
+    /// Structs with `#[serde(flatten)]` fields are deserialized as
+    /// maps, so entries written like struct fields (framed by
+    /// `FieldBegin`/`FieldEnd`) are supported as well, see [`MapKey`].
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.parse_type(DataType::Int)?;
-        let length = self.parse_i32()? as usize;
-        visitor.visit_map(LengthBased::new(self, length))
+        let length = self.parse_length()?;
+        self.nested(|de| visitor.visit_map(LengthBased::new(de, length)))
     }
 
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.parse_type(DataType::Int)?;
-        let length = self.parse_i32()? as usize;
-        visitor.visit_map(LengthBasedStruct::new(self, length))
+        let length = self.parse_field_count()?;
+        self.nested(|de| visitor.visit_map(LengthBasedStruct::new(de, length, fields)))
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
@@ -356,11 +882,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
             self.next_byte()?;
             visitor.visit_enum(self.parse_string()?.into_deserializer())
         } else if next == DataType::Int {
-            todo!()
+            self.next_byte()?;
+            let index = self.parse_i32()?;
+            if index < 0 || index as usize >= variants.len() {
+                return Err(Error::BadValue {
+                    pos: self.counter - 4,
+                });
+            }
+            visitor.visit_enum((index as u32).into_deserializer())
         } else if next == DataType::FieldBegin {
-            let value = visitor.visit_enum(Enum::new(self))?;
-            self.parse_type(DataType::FieldEnd)?;
-            Ok(value)
+            self.nested(|de| {
+                let value = visitor.visit_enum(Enum::new(de))?;
+                de.parse_type(DataType::FieldEnd)?;
+                Ok(value)
+            })
         } else {
             Err(Error::Unexpected {
                 want: None,
@@ -370,36 +905,41 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         }
     }
 
+    /// Names (of fields and enum variants) follow a `FieldBegin` and
+    /// have no data type.
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        visitor.visit_borrowed_str(self.parse_string()?)
     }
 
+    /// Skips the value without building it.
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.skip_value()?;
+        visitor.visit_unit()
     }
 
     fn deserialize_unit_struct<V>(
         self,
         _name: &'static str,
-        _visitor: V,
-    ) -> std::result::Result<V::Value, Self::Error>
+        visitor: V,
+    ) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_unit(visitor)
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> std::result::Result<V::Value, Self::Error>
+    fn deserialize_unit<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.parse_type(DataType::FieldEnd)?;
+        visitor.visit_unit()
     }
 }
 
@@ -407,18 +947,24 @@ struct LengthBasedStruct<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     total: usize,
     done: usize,
+    fields: &'static [&'static str],
 }
 
 impl<'a, 'de> LengthBasedStruct<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>, total: usize) -> Self {
-        Self { de, total, done: 0 }
+    fn new(de: &'a mut Deserializer<'de>, total: usize, fields: &'static [&'static str]) -> Self {
+        Self {
+            de,
+            total,
+            done: 0,
+            fields,
+        }
     }
 }
 
 impl<'de, 'a> MapAccess<'de> for LengthBasedStruct<'a, 'de> {
     type Error = Error;
 
-    fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+    fn next_key_seed<K>(&mut self, seed: K) -> core::result::Result<Option<K::Value>, Self::Error>
     where
         K: DeserializeSeed<'de>,
     {
@@ -430,16 +976,26 @@ impl<'de, 'a> MapAccess<'de> for LengthBasedStruct<'a, 'de> {
         } else {
             self.done += 1;
             self.de.parse_type(DataType::FieldBegin)?;
-            let key = DeserializeSeed::deserialize(seed, &mut *self.de)?;
+            let pos = self.de.counter;
+            let deny_unknown_fields = self.de.deny_unknown_fields;
+            let name = self.de.parse_string()?;
+            let name = self.de.field_names.get(name).map_or(name, String::as_str);
+            if deny_unknown_fields && !self.fields.contains(&name) {
+                return Err(Error::UnknownField {
+                    name: name.to_string(),
+                    pos,
+                });
+            }
+            let key = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name))?;
             Ok(Some(key))
         }
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
+    fn next_value_seed<V>(&mut self, seed: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        seed.deserialize(FieldValue { de: &mut *self.de })
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -447,38 +1003,131 @@ impl<'de, 'a> MapAccess<'de> for LengthBasedStruct<'a, 'de> {
     }
 }
 
+/// Deserializer for the value of a struct field. A field may hold
+/// several values (e.g. a tuple struct), so when the type is not known
+/// (`deserialize_any`, `deserialize_ignored_any`) all of the values up
+/// to the `FieldEnd` are given as a sequence.
+struct FieldValue<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+macro_rules! forward_to_inner {
+    ($($method:ident($($arg:ident: $ty:ty),*)),+) => {
+        $(fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+        where
+            V: Visitor<'de>,
+        {
+            de::Deserializer::$method(self.de, $($arg,)* visitor)
+        })+
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldValue<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Terminated::new(self.de, None))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        while self.de.peek_next_datatype()? != DataType::FieldEnd {
+            self.de.skip_value()?;
+        }
+        visitor.visit_unit()
+    }
+
+    forward_to_inner! {
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier()
+    }
+}
+
 struct LengthBased<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     total: usize,
     done: usize,
+    /// Encoded keys read so far when reading a map, a key appearing
+    /// twice would otherwise silently replace the first value.
+    keys: BTreeSet<&'de [u8]>,
+    /// Whether the current map entry is framed like a struct field.
+    framed: bool,
 }
 
 impl<'a, 'de> LengthBased<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>, total: usize) -> Self {
-        Self { de, total, done: 0 }
+        Self {
+            de,
+            total,
+            done: 0,
+            keys: BTreeSet::new(),
+            framed: false,
+        }
     }
 }
 
 impl<'de, 'a> MapAccess<'de> for LengthBased<'a, 'de> {
     type Error = Error;
 
-    fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+    fn next_key_seed<K>(&mut self, seed: K) -> core::result::Result<Option<K::Value>, Self::Error>
     where
         K: DeserializeSeed<'de>,
     {
         if self.done == self.total {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut *self.de).map(Some)
+            return Ok(None);
         }
+        let (pos, input) = (self.de.counter, self.de.input);
+        let key = seed.deserialize(MapKey {
+            de: &mut *self.de,
+            framed: &mut self.framed,
+        })?;
+        if !self.keys.insert(&input[..self.de.counter - pos]) {
+            return Err(Error::DuplicateKey { pos });
+        }
+        Ok(Some(key))
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error>
+    fn next_value_seed<V>(&mut self, seed: V) -> core::result::Result<V::Value, Self::Error>
     where
         V: DeserializeSeed<'de>,
     {
         self.done += 1;
-        seed.deserialize(&mut *self.de)
+        if !core::mem::take(&mut self.framed) {
+            return seed.deserialize(&mut *self.de);
+        }
+        let value = seed.deserialize(FieldValue { de: &mut *self.de })?;
+        self.de.parse_type(DataType::FieldEnd)?;
+        Ok(value)
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -486,6 +1135,88 @@ impl<'de, 'a> MapAccess<'de> for LengthBased<'a, 'de> {
     }
 }
 
+/// Deserializer for map keys. Keys are values like any other, except
+/// that a string key may also be the name of an entry written like a
+/// struct field (as for structs with `#[serde(flatten)]` fields), which
+/// is then followed by the value and a `FieldEnd`. A `String` value
+/// cannot start with a `FieldBegin`, so only string keys check for one.
+struct MapKey<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    framed: &'a mut bool,
+}
+
+impl<'a, 'de> MapKey<'a, 'de> {
+    fn name<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.de.peek_next_datatype()? != DataType::FieldBegin {
+            return de::Deserializer::deserialize_str(self.de, visitor);
+        }
+        self.de.next_byte()?;
+        *self.framed = true;
+        let name = self.de.parse_string()?;
+        match self.de.field_names.get(name) {
+            Some(name) => visitor.visit_str(name),
+            None => visitor.visit_borrowed_str(name),
+        }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for MapKey<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.name(visitor)
+    }
+
+    forward_to_inner! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_ignored_any()
+    }
+}
+
 impl<'de, 'a> SeqAccess<'de> for LengthBased<'a, 'de> {
     type Error = Error;
 
@@ -526,20 +1257,133 @@ impl<'de, 'a> SeqAccess<'de> for Terminated<'a, 'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        if self.de.peek_next_datatype()? == DataType::FieldEnd {
-            if let Some(total) = self.total {
-                if self.done == total {
+        // When the length is known only the element count ends the
+        // sequence, a `FieldEnd` here may belong to a nested value (or be
+        // an absent trailing optional.) Otherwise the end of the input
+        // ends it too, a missing `FieldEnd` is reported by the caller.
+        match self.total {
+            Some(total) if self.done == total => return Ok(None),
+            Some(_) => {}
+            None => {
+                if self.de.input.is_empty() || self.de.peek_next_datatype()? == DataType::FieldEnd {
                     return Ok(None);
                 }
-            } else {
-                return Ok(None);
             }
         }
 
+        self.done += 1;
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+/// Every value remaining in the input.
+struct Document<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'a, 'de> Document<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Document { de }
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for Document<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.de.input.is_empty() {
+            return Ok(None);
+        }
+
         seed.deserialize(&mut *self.de).map(Some)
     }
 }
 
+impl<'de, 'a> de::Deserializer<'de> for Document<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// The name and values between a `FieldBegin` and its `FieldEnd`, as
+/// a single map entry.
+struct Object<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    done: bool,
+}
+
+impl<'a, 'de> Object<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Object { de, done: false }
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for Object<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        let name: &str = self.de.parse_string()?;
+        seed.deserialize(IntoDeserializer::<Error>::into_deserializer(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(Fields { de: &mut *self.de })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(if self.done { 0 } else { 1 })
+    }
+}
+
+/// Deserializes the values of an object up to (not including) its
+/// `FieldEnd` as a sequence.
+struct Fields<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Fields<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Terminated::new(self.de, None))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
 struct Enum<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
 }
@@ -589,20 +1433,25 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
         Ok(value)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        let length = self.de.parse_field_count()?;
+        visitor.visit_map(LengthBasedStruct::new(self.de, length, fields))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
     use linked_hash_map::LinkedHashMap;
 
     use super::*;
-    use crate::DataType;
+    use crate::{DataType, KrdsValue};
 
     use kindle_formats::krds::*;
 
@@ -619,11 +1468,12 @@ mod test {
     }
 
     macro_rules! de_num_test {
-        {$($num:expr => $name:ident $dtype:expr),+} => {
+        {$($ty:ty: $num:expr => $name:ident $dtype:expr),+} => {
             $(#[test]
               fn $name() {
                   let bytes = test_num($num, $dtype);
-                  assert!($num == de_no_magic(&bytes))
+                  let v: $ty = de_no_magic(&bytes);
+                  assert_eq!(v, $num)
             })+
         };
     }
@@ -644,19 +1494,706 @@ mod test {
         );
     }
 
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct BorrowedStruct<'a> {
+        field_1: i32,
+        field_2: &'a str,
+    }
+
+    #[test]
+    fn de_borrowed_str() {
+        let (bytes, _) = simple_struct();
+        assert_eq!(
+            de_no_magic::<BorrowedStruct>(&bytes),
+            BorrowedStruct {
+                field_1: 1234,
+                field_2: "testing stuff",
+            }
+        );
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct CowStruct<'a> {
+        field_1: i32,
+        #[serde(borrow)]
+        field_2: Cow<'a, str>,
+    }
+
+    #[test]
+    fn de_borrowed_cow() {
+        let (bytes, _) = simple_struct();
+        let value = de_no_magic::<CowStruct>(&bytes);
+        assert!(matches!(value.field_2, Cow::Borrowed("testing stuff")));
+    }
+
+    #[test]
+    fn de_bool_from_int() {
+        assert!(de_no_magic::<bool>(&test_num(1i32, DataType::Int)));
+        assert!(!de_no_magic::<bool>(&test_num(0i32, DataType::Int)));
+
+        let bytes = test_num(2i32, DataType::Int);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            bool::deserialize(&mut deserializer),
+            Err(Error::BadValue { pos: 1 })
+        ));
+    }
+
+    #[test]
+    fn de_negative_length() {
+        let bytes = test_num(-1i32, DataType::Int);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            Vec::<i32>::deserialize(&mut deserializer),
+            Err(Error::InvalidLength { len: -1, pos: 0 })
+        ));
+    }
+
+    #[test]
+    fn de_length_past_end() {
+        let bytes = [
+            test_num(i32::MAX, DataType::Int),
+            test_num(1i32, DataType::Int),
+        ]
+        .concat();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            SimpleStruct::deserialize(&mut deserializer),
+            Err(Error::InvalidLength {
+                len: i32::MAX,
+                pos: 0
+            })
+        ));
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            Vec::<i32>::deserialize(&mut deserializer),
+            Err(Error::Eof { pos: 10 })
+        ));
+    }
+
+    #[test]
+    fn de_max_container_len() {
+        let (bytes, data) = test_vec_int();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        deserializer.set_max_container_len(data.len() - 1);
+        assert!(matches!(
+            Vec::<i32>::deserialize(&mut deserializer),
+            Err(Error::LengthLimitExceeded { len, limit, pos: 0 })
+                if len == data.len() && limit == data.len() - 1
+        ));
+
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        deserializer.set_max_container_len(data.len());
+        assert_eq!(Vec::<i32>::deserialize(&mut deserializer).unwrap(), data);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct RenamedStruct {
+        field_1: i32,
+        text: String,
+    }
+
+    #[test]
+    fn de_field_name_map() {
+        let (bytes, _) = simple_struct();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(RenamedStruct::deserialize(&mut deserializer).is_err());
+
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        deserializer.set_deny_unknown_fields(true);
+        deserializer.set_field_name_map(FieldNameMap::from([(
+            "field_2".to_string(),
+            "text".to_string(),
+        )]));
+        assert_eq!(
+            RenamedStruct::deserialize(&mut deserializer).unwrap(),
+            RenamedStruct {
+                field_1: 1234,
+                text: test_string().1,
+            }
+        );
+    }
+
+    #[test]
+    fn de_duplicate_key() {
+        let entry = |key: i32, value: i32| {
+            [test_num(key, DataType::Int), test_num(value, DataType::Int)].concat()
+        };
+        let bytes = [test_num(2i32, DataType::Int), entry(0, 1), entry(0, 2)].concat();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            HashMap::<i32, i32>::deserialize(&mut deserializer),
+            Err(Error::DuplicateKey { pos: 15 })
+        ));
+
+        let field = |value: i32| {
+            [
+                &[DataType::FieldBegin as u8, 0, 0, 1, b'a'] as &[_],
+                &test_num(value, DataType::Int),
+                &[DataType::FieldEnd as u8],
+            ]
+            .concat()
+        };
+        let bytes = [test_num(2i32, DataType::Int), field(1), field(2)].concat();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            HashMap::<String, i32>::deserialize(&mut deserializer),
+            Err(Error::DuplicateKey { pos: 16 })
+        ));
+    }
+
+    #[test]
+    fn de_nested() {
+        let (blob, data) = simple_struct();
+        let file = [crate::MAGIC as &[_], &blob].concat();
+        for (blob, magic) in [(&blob, false), (&file, true)] {
+            let bytes = [
+                test_num(blob.len() as i32, DataType::Int),
+                blob.clone(),
+                test_num(8i32, DataType::Int),
+            ]
+            .concat();
+            let mut deserializer = Deserializer::from_bytes(&bytes);
+            let len = i32::deserialize(&mut deserializer).unwrap() as usize;
+            assert_eq!(
+                deserializer
+                    .read_nested::<SimpleStruct>(len, magic)
+                    .unwrap(),
+                data
+            );
+            assert_eq!(i32::deserialize(&mut deserializer).unwrap(), 8);
+            assert!(deserializer.end().is_ok());
+        }
+
+        let mut deserializer = Deserializer::from_bytes(&blob);
+        assert!(matches!(
+            deserializer.read_nested::<SimpleStruct>(blob.len(), true),
+            Err(Error::BadMagic)
+        ));
+        let mut deserializer = Deserializer::from_bytes(&blob);
+        assert!(matches!(
+            deserializer.read_nested::<i32>(blob.len(), false),
+            Err(Error::TrailingBytes { pos: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn de_eof() {
+        let bytes = [DataType::Int as u8, 0, 0];
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            i32::deserialize(&mut deserializer),
+            Err(Error::UnexpectedEof {
+                needed: 4,
+                got: 2,
+                pos: 1
+            })
+        ));
+
+        let mut deserializer = Deserializer::from_bytes(&[]);
+        assert!(matches!(
+            bool::deserialize(&mut deserializer),
+            Err(Error::Eof { pos: 0 })
+        ));
+    }
+
+    #[test]
+    fn get_array_len() {
+        let mut deserializer = Deserializer::from_bytes(&[1, 2, 3, 4]);
+        assert_eq!(deserializer.get_array::<4>().unwrap(), [1, 2, 3, 4]);
+        assert!(deserializer.end().is_ok());
+
+        let mut deserializer = Deserializer::from_bytes(&[1, 2, 3]);
+        assert!(matches!(
+            deserializer.get_array::<4>(),
+            Err(Error::UnexpectedEof {
+                needed: 4,
+                got: 3,
+                pos: 0
+            })
+        ));
+        assert_eq!(deserializer.get_array::<3>().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn de_truncated_long() {
+        let bytes = test_num(1i64, DataType::Long);
+        let mut deserializer = Deserializer::from_bytes(&bytes[..6]);
+        assert!(matches!(
+            i64::deserialize(&mut deserializer),
+            Err(Error::UnexpectedEof {
+                needed: 8,
+                got: 5,
+                pos: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn de_i128_unsupported() {
+        let bytes = test_num(1i64, DataType::Long);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            i128::deserialize(&mut deserializer),
+            Err(Error::UnsupportedType("i128"))
+        ));
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            u128::deserialize(&mut deserializer),
+            Err(Error::UnsupportedType("u128"))
+        ));
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct VecMapTail {
+        field_4: LinkedHashMap<NoteType, String>,
+    }
+
+    #[test]
+    fn de_ignored_fields() {
+        let (bytes, _) = vec_map_struct();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert_eq!(
+            VecMapTail::deserialize(&mut deserializer).unwrap(),
+            VecMapTail {
+                field_4: test_map().1
+            }
+        );
+        deserializer.end().unwrap();
+    }
+
+    #[test]
+    fn de_ignored_any() {
+        let (bytes, _) = simple_newtype();
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        de::IgnoredAny::deserialize(&mut deserializer).unwrap();
+        deserializer.end().unwrap();
+
+        // Only the first value (the field count) of a document.
+        let mut deserializer = Deserializer::with_magic(PDFANNOT_YJR).unwrap();
+        de::IgnoredAny::deserialize(&mut deserializer).unwrap();
+        assert_eq!(deserializer.counter, crate::MAGIC.len() + 5);
+
+        from_document::<de::IgnoredAny>(PDFANNOT_YJR).unwrap();
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Small {
+        a: i32,
+    }
+
+    /// Deeply nested unknown fields are skipped without running out of
+    /// stack.
+    #[test]
+    fn de_ignored_deeply_nested() {
+        let nested = [DataType::FieldBegin as u8, crate::EMPTY_STRING].repeat(200_000);
+        let bytes = [
+            crate::MAGIC as &[_],
+            &test_num(1i32, DataType::Int),
+            &[DataType::FieldBegin as u8, 0, 0, 1, b'b'],
+            &nested,
+        ]
+        .concat();
+        assert!(matches!(
+            from_bytes::<Small>(&bytes),
+            Err(Error::Eof { pos }) if pos == bytes.len()
+        ));
+    }
+
+    /// Found by fuzzing, used to overflow the stack.
+    #[test]
+    fn de_any_recursion_limit() {
+        let nested = [DataType::FieldBegin as u8, crate::EMPTY_STRING].repeat(100_000);
+        let bytes = [crate::MAGIC as &[_], &nested].concat();
+        assert!(matches!(
+            from_bytes::<KrdsValue>(&bytes),
+            Err(Error::RecursionLimitExceeded { .. })
+        ));
+
+        let nested = [DataType::FieldBegin as u8, crate::EMPTY_STRING].repeat(100);
+        let end = [DataType::FieldEnd as u8].repeat(100);
+        let bytes = [crate::MAGIC as &[_], &nested, &end].concat();
+        assert!(from_bytes::<KrdsValue>(&bytes).is_ok());
+    }
+
+    #[test]
+    fn de_any_nested_object() {
+        let (inner_bytes, _) = simple_newtype();
+        let bytes = [
+            &[DataType::FieldBegin as u8, 0, 0, 5] as &[_],
+            b"outer",
+            &inner_bytes,
+            &test_num(3i32, DataType::Int),
+            &[DataType::FieldEnd as u8],
+        ]
+        .concat();
+        assert_eq!(
+            de_no_magic::<KrdsValue>(&bytes),
+            KrdsValue::Object {
+                name: "outer".to_string(),
+                fields: vec![
+                    KrdsValue::Object {
+                        name: "page.history.record".to_string(),
+                        fields: vec![KrdsValue::String(test_string().1), KrdsValue::Long(7734)],
+                    },
+                    KrdsValue::Int(3),
+                ],
+            }
+        );
+
+        let bytes = [DataType::FieldEnd as u8];
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            KrdsValue::deserialize(&mut deserializer),
+            Err(Error::Unexpected {
+                want: None,
+                got: DataType::FieldEnd,
+                pos: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn de_invalid_utf8() {
+        let bytes = [DataType::String as u8, 0, 0, 2, 0xff, 0xfe];
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            String::deserialize(&mut deserializer),
+            Err(Error::InvalidUtf8 { pos: 4, bytes }) if bytes == [0xff, 0xfe]
+        ));
+    }
+
+    #[test]
+    fn sniff_file_types() {
+        assert_eq!(sniff_file_type(PDFANNOT_YJR).unwrap(), FileKind::ReaderData);
+        assert_eq!(sniff_file_type(PDFANNOT_YJF).unwrap(), FileKind::TimerData);
+        assert_eq!(
+            sniff_file_type(BOOK_HL_NOTE_AZW3R).unwrap(),
+            FileKind::ReaderData
+        );
+        assert_eq!(
+            sniff_file_type(BOOK_HL_NOTE_AZW3F).unwrap(),
+            FileKind::TimerData
+        );
+    }
+
+    #[test]
+    fn read_headers() {
+        let header = read_header(PDFANNOT_YJR).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(
+            header.fields,
+            [
+                "next.in.series.info.data",
+                "annotation.cache.object",
+                "language.store",
+                "ReaderMetrics"
+            ]
+        );
+        assert_eq!(
+            read_header(PDFANNOT_YJF).unwrap().fields,
+            [
+                "timer.model",
+                "fpr",
+                "book.info.store",
+                "page.history.store",
+                "whisperstore.migration.status",
+                "lpr"
+            ]
+        );
+        assert_eq!(
+            read_header(BOOK_HL_NOTE_AZW3R).unwrap().fields,
+            [
+                "font.prefs",
+                "sync_lpr",
+                "next.in.series.info.data",
+                "annotation.cache.object",
+                "apnx.key",
+                "ReaderMetrics"
+            ]
+        );
+
+        let mut bytes = PDFANNOT_YJR.to_vec();
+        bytes[crate::MAGIC.len() - 1] = 2;
+        let header = read_header(&bytes).unwrap();
+        assert_eq!(header.version, 2);
+        assert_eq!(header.fields.len(), 4);
+    }
+
+    #[test]
+    fn sniff_unknown_fields() {
+        let bytes = crate::ser::to_bytes(&vec_map_struct().1).unwrap();
+        assert_eq!(sniff_file_type(&bytes).unwrap(), FileKind::Unknown);
+    }
+
+    #[test]
+    fn pdfannot_yjr_de_reader() {
+        assert_eq!(
+            from_reader::<_, ReaderDataFile>(PDFANNOT_YJR).unwrap(),
+            pdfannot_yjr()
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        let mut truncated = PDFANNOT_YJF.to_vec();
+        truncated.pop();
+        assert!(matches!(
+            from_bytes::<TimerDataFile>(&truncated),
+            Err(Error::Eof { pos }) if pos == truncated.len()
+        ));
+
+        let trailing = [PDFANNOT_YJF, &[0, 0]].concat();
+        assert!(matches!(
+            from_bytes::<TimerDataFile>(&trailing),
+            Err(Error::TrailingBytes { pos, remaining: 2 }) if pos == PDFANNOT_YJF.len()
+        ));
+
+        let bytes = [DataType::Int as u8, 0, 0, 0, 1, 42];
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            <(i32, i32)>::deserialize(&mut deserializer),
+            Err(Error::UnknownType {
+                value: 42,
+                pos: Some(5)
+            })
+        ));
+    }
+
+    #[test]
+    fn magic_version() {
+        assert_eq!(
+            &[crate::SIGNATURE as &[_], &crate::VERSION.to_be_bytes()].concat(),
+            crate::MAGIC
+        );
+
+        let mut bytes = PDFANNOT_YJF.to_vec();
+        bytes[crate::MAGIC.len() - 1] = 2;
+        assert!(matches!(
+            from_bytes::<TimerDataFile>(&bytes),
+            Err(Error::UnsupportedVersion(2))
+        ));
+
+        bytes[0] = 1;
+        assert!(matches!(
+            from_bytes::<TimerDataFile>(&bytes),
+            Err(Error::BadMagic)
+        ));
+    }
+
+    /// KRDS is written by Java's `DataOutputStream`, which is big
+    /// endian regardless of the platform.
+    #[test]
+    fn big_endian() {
+        let count = &PDFANNOT_YJR[crate::MAGIC.len()..crate::MAGIC.len() + 5];
+        assert_eq!(count, [DataType::Int as u8, 0, 0, 0, 4]);
+        assert_eq!(de_no_magic::<i32>(count), 4);
+        assert_eq!(
+            de_no_magic::<i64>(&[DataType::Long as u8, 0, 0, 0, 0, 0, 0, 1, 2]),
+            0x0102
+        );
+        assert_eq!(ser_no_magic(0x0102_i16), [DataType::Short as u8, 1, 2]);
+    }
+
+    #[test]
+    fn de_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), PDFANNOT_YJR).unwrap();
+        assert_eq!(
+            from_file::<_, ReaderDataFile>(file.path()).unwrap(),
+            from_bytes::<ReaderDataFile>(PDFANNOT_YJR).unwrap()
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            from_file::<_, ReaderDataFile>(dir.path().join("missing.yjr")),
+            Err(Error::ReadError(_))
+        ));
+    }
+
+    #[test]
+    fn owned_deserializer() {
+        let deserializer = OwnedDeserializer::from_vec(PDFANNOT_YJR.to_vec());
+        assert_eq!(
+            deserializer.deserialize::<ReaderDataFile>().unwrap(),
+            from_bytes::<ReaderDataFile>(PDFANNOT_YJR).unwrap()
+        );
+        assert_eq!(deserializer.into_inner(), PDFANNOT_YJR);
+    }
+
+    #[test]
+    fn scan_for_magic() {
+        let bytes = [b"\xef\xbb\xbf" as &[_], PDFANNOT_YJR].concat();
+        assert!(matches!(
+            from_bytes::<ReaderDataFile>(&bytes),
+            Err(Error::BadMagic)
+        ));
+        let (value, skipped) = from_bytes_scan::<ReaderDataFile>(&bytes, 16).unwrap();
+        assert_eq!(value, from_bytes::<ReaderDataFile>(PDFANNOT_YJR).unwrap());
+        assert_eq!(skipped, 3);
+
+        assert_eq!(
+            from_bytes_scan::<ReaderDataFile>(PDFANNOT_YJR, 0)
+                .unwrap()
+                .1,
+            0
+        );
+        assert!(matches!(
+            from_bytes_scan::<ReaderDataFile>(&bytes, 2),
+            Err(Error::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn minimal_file() {
+        let bytes = [crate::MAGIC as &[_], &[DataType::Boolean as u8, 1]].concat();
+        assert_eq!(crate::ser::to_bytes(&true).unwrap(), bytes);
+        assert!(from_bytes::<bool>(&bytes).unwrap());
+
+        assert_eq!(from_bytes::<Option<i32>>(crate::MAGIC).unwrap(), None);
+        assert!(matches!(
+            from_bytes::<Option<i32>>(&crate::MAGIC[..16]),
+            Err(Error::Eof { pos: 16 })
+        ));
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct TimerDataNoModel {
+        fpr: Option<FPR>,
+        #[serde(rename = "book.info.store")]
+        book_info_store: Option<BookInfoStore>,
+        #[serde(rename = "page.history.store")]
+        page_history_store: Option<Vec<PHRWrapper>>,
+        #[serde(rename = "whisperstore.migration.status")]
+        whisperstore_migration_status: Option<WhisperstoreMigrationStatus>,
+        lpr: Option<LPR>,
+    }
+
+    #[test]
+    fn strict_unknown_field() {
+        assert!(from_bytes_strict::<TimerDataFile>(PDFANNOT_YJF).is_ok());
+        assert!(from_bytes::<TimerDataNoModel>(PDFANNOT_YJF).is_ok());
+        assert!(matches!(
+            from_bytes_strict::<TimerDataNoModel>(PDFANNOT_YJF),
+            Err(Error::UnknownField { name, pos: 23 }) if name == "timer.model"
+        ));
+    }
+
+    #[test]
+    fn partial_de() {
+        let bytes = [PDFANNOT_YJF, PDFANNOT_YJF].concat();
+        let (first, rest) = from_bytes_partial::<TimerDataFile>(&bytes).unwrap();
+        assert_eq!(first, pdfannot_yjf());
+        assert_eq!(rest, PDFANNOT_YJF);
+        assert_eq!(from_bytes::<TimerDataFile>(rest).unwrap(), pdfannot_yjf());
+    }
+
+    /// KRDS has no half precision type, a `half::f16` is read from a
+    /// `Float` and rounded to the nearest half, which loses precision
+    /// for most values.
+    #[derive(Debug, PartialEq)]
+    struct Half(half::f16);
+
+    impl<'de> Deserialize<'de> for Half {
+        fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            f32::deserialize(deserializer).map(|v| Half(half::f16::from_f32(v)))
+        }
+    }
+
+    #[test]
+    fn float_to_half() {
+        let bytes = test_num(1.5_f32, DataType::Float);
+        assert_eq!(de_no_magic::<Half>(&bytes).0.to_f32(), 1.5);
+
+        let bytes = test_num(0.1_f32, DataType::Float);
+        let half = de_no_magic::<Half>(&bytes).0;
+        assert_eq!(half, half::f16::from_f32(0.1));
+        assert_ne!(half.to_f32(), 0.1);
+
+        let bytes = test_num(f32::MAX, DataType::Float);
+        assert_eq!(de_no_magic::<Half>(&bytes).0, half::f16::INFINITY);
+    }
+
+    #[test]
+    fn float_widening() {
+        let bytes = test_num(3.25_f32, DataType::Float);
+        assert_eq!(de_no_magic::<f64>(&bytes), 3.25);
+        let bytes = test_num(3.25_f64, DataType::Double);
+        assert_eq!(de_no_magic::<f32>(&bytes), 3.25);
+
+        let bytes = test_num(0.1_f64, DataType::Double);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            f32::deserialize(&mut deserializer),
+            Err(Error::BadValue { pos: 1 })
+        ));
+
+        let bytes = test_num(3_i32, DataType::Int);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert_eq!(
+            f64::deserialize(&mut deserializer).unwrap_err().to_string(),
+            "expected Double, got Int at byte 1"
+        );
+    }
+
+    #[test]
+    fn map_de_hash_map() {
+        let (bytes, data) = test_map();
+        assert_eq!(
+            de_no_magic::<HashMap<NoteType, String>>(&bytes),
+            data.into_iter().collect::<HashMap<_, _>>()
+        );
+    }
+
+    #[test]
+    fn short_note_type() {
+        let bytes = [
+            &test_num(1i32, DataType::Int) as &[_],
+            &test_num(NoteType::Handwritten as i16, DataType::Short),
+            &test_string().0,
+        ]
+        .concat();
+        let map = de_no_magic::<LinkedHashMap<NoteType, String>>(&bytes);
+        assert_eq!(map.get(&NoteType::Handwritten), Some(&test_string().1));
+    }
+
+    #[test]
+    fn int_enum_out_of_range() {
+        let bytes = test_num(3i32, DataType::Int);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            SimpleEnum::deserialize(&mut deserializer),
+            Err(Error::BadValue { pos: 1 })
+        ));
+        let bytes = test_num(-1i32, DataType::Int);
+        let mut deserializer = Deserializer::from_bytes(&bytes);
+        assert!(matches!(
+            SimpleEnum::deserialize(&mut deserializer),
+            Err(Error::BadValue { pos: 1 })
+        ));
+    }
+
     de_num_test! {
-        117_i8 => de_i8 DataType::Byte,
-        2004_i16 => de_i16 DataType::Short,
-        65555_i32 => de_i32 DataType::Int,
-        4294967300_i64 => de_i64 DataType::Long,
-        3.14_f32 => de_f32 DataType::Float,
-        1293842345.00000000213_f64 => de_f64 DataType::Double
+        i8: 117_i8 => de_i8 DataType::Byte,
+        i16: 2004_i16 => de_i16 DataType::Short,
+        i32: 65555_i32 => de_i32 DataType::Int,
+        i64: 4294967300_i64 => de_i64 DataType::Long,
+        f32: 3.14_f32 => de_f32 DataType::Float,
+        f64: 1293842345.00000000213_f64 => de_f64 DataType::Double
     }
 
     de_test! {
         SimpleStruct => simple_struct_de simple_struct,
+        UnitStruct => unit_struct_de unit_struct,
+        SimpleEnum => int_enum_de int_enum,
+        StructVariantEnum => struct_variant_de struct_variant,
         PHRWrapper => simple_newtype_de simple_newtype,
         String => string_de test_string,
+        char => char_de test_char,
         String => empty_string_de empty_string,
         Vec<i32> => int_vec_de test_vec_int,
         Vec<String> => string_vec_de test_vec_strings,
@@ -0,0 +1,139 @@
+//! Structural comparison of KRDS files, for finding out why a round
+//! trip is not byte for byte identical.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::error::Result;
+use crate::value::KrdsValue;
+
+/// A value that differs between two files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffEntry {
+    /// Position of the value, `[n]` for the nth value of a list or
+    /// object and the object name when entering one.
+    pub path: Vec<String>,
+    /// `None` if the value is missing from the first file.
+    pub left: Option<KrdsValue>,
+    /// `None` if the value is missing from the second file.
+    pub right: Option<KrdsValue>,
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_fmt(format_args!(
+            "{}: {:?} != {:?}",
+            self.path.join(" > "),
+            self.left,
+            self.right
+        ))
+    }
+}
+
+/// Parse two KRDS files into [`KrdsValue`] trees and list the values
+/// that differ. Objects with the same name are compared field by
+/// field, anything else is reported as a whole.
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<DiffEntry>> {
+    let mut entries = Vec::new();
+    diff_values(
+        &mut Vec::new(),
+        &KrdsValue::List(KrdsValue::from_document(a)?),
+        &KrdsValue::List(KrdsValue::from_document(b)?),
+        &mut entries,
+    );
+    Ok(entries)
+}
+
+fn diff_values(path: &mut Vec<String>, a: &KrdsValue, b: &KrdsValue, entries: &mut Vec<DiffEntry>) {
+    if a == b {
+        return;
+    }
+    match (a, b) {
+        (
+            KrdsValue::Object {
+                name: name_a,
+                fields: fields_a,
+            },
+            KrdsValue::Object {
+                name: name_b,
+                fields: fields_b,
+            },
+        ) if name_a == name_b => {
+            path.push(name_a.clone());
+            diff_lists(path, fields_a, fields_b, entries);
+            path.pop();
+        }
+        (KrdsValue::List(a), KrdsValue::List(b)) => diff_lists(path, a, b, entries),
+        _ => entries.push(DiffEntry {
+            path: path.clone(),
+            left: Some(a.clone()),
+            right: Some(b.clone()),
+        }),
+    }
+}
+
+fn diff_lists(
+    path: &mut Vec<String>,
+    a: &[KrdsValue],
+    b: &[KrdsValue],
+    entries: &mut Vec<DiffEntry>,
+) {
+    for i in 0..a.len().max(b.len()) {
+        path.push(format!("[{}]", i));
+        match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => diff_values(path, a, b, entries),
+            (left, right) => entries.push(DiffEntry {
+                path: path.clone(),
+                left: left.cloned(),
+                right: right.cloned(),
+            }),
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test::*;
+    use crate::to_bytes;
+
+    #[test]
+    fn diff_same() {
+        assert_eq!(diff(PDFANNOT_YJR, PDFANNOT_YJR).unwrap(), []);
+    }
+
+    #[test]
+    fn diff_modified() {
+        let mut values = KrdsValue::from_document(PDFANNOT_YJR).unwrap();
+        values[3] = KrdsValue::Object {
+            name: "language.store".to_string(),
+            fields: vec![KrdsValue::String("fr-FR".to_string()), KrdsValue::Int(4)],
+        };
+        values.push(KrdsValue::Int(1));
+        let modified = to_bytes(&KrdsValue::List(values)).unwrap();
+
+        assert_eq!(
+            diff(PDFANNOT_YJR, &modified).unwrap(),
+            [
+                DiffEntry {
+                    path: vec![
+                        "[3]".to_string(),
+                        "language.store".to_string(),
+                        "[0]".to_string()
+                    ],
+                    left: Some(KrdsValue::String("en-US".to_string())),
+                    right: Some(KrdsValue::String("fr-FR".to_string())),
+                },
+                DiffEntry {
+                    path: vec!["[5]".to_string()],
+                    left: None,
+                    right: Some(KrdsValue::Int(1)),
+                }
+            ]
+        );
+    }
+}
@@ -1,20 +1,43 @@
-use std::fmt::{self, Display};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
 
 use serde::{de, ser};
 
 use crate::DataType;
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Message(String),
-    Eof,
-    UnknownType(i8),
+    Eof {
+        pos: usize,
+    },
+    /// A value at `pos` needed more bytes than were left.
+    UnexpectedEof {
+        needed: usize,
+        got: usize,
+        pos: usize,
+    },
+    /// `pos` is only known when the type byte was read by the
+    /// deserializer.
+    UnknownType {
+        value: i8,
+        pos: Option<usize>,
+    },
+    #[cfg(feature = "std")]
     ReadError(std::io::Error),
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
     BadMagic,
-    BadValue,
+    UnsupportedVersion(u64),
+    BadValue {
+        pos: usize,
+    },
     WontImplement,
+    /// A Rust type with no KRDS equivalent.
+    UnsupportedType(&'static str),
     Unexpected {
         want: Option<DataType>,
         got: DataType,
@@ -26,7 +49,56 @@ pub enum Error {
         pos: usize,
     },
     ExpectedIntervalTree,
-    TrailingBytes,
+    UnencodableChar(char),
+    StringTooLong {
+        len: usize,
+    },
+    /// Sequences, maps and structs are prefixed with an `i32` length.
+    SequenceTooLong {
+        len: usize,
+    },
+    /// `bytes` are the contents of the string starting at `pos`.
+    InvalidUtf8 {
+        pos: usize,
+        bytes: Vec<u8>,
+    },
+    /// A length prefix at `pos` that is negative or longer than the rest
+    /// of the input.
+    InvalidLength {
+        len: i32,
+        pos: usize,
+    },
+    /// A length prefix at `pos` longer than the limit set with
+    /// `Deserializer::set_max_container_len`.
+    LengthLimitExceeded {
+        len: usize,
+        limit: usize,
+        pos: usize,
+    },
+    UnknownField {
+        name: String,
+        pos: usize,
+    },
+    /// The key of a map entry starting at `pos` was already read.
+    DuplicateKey {
+        pos: usize,
+    },
+    /// A tuple struct field was (or ended with) `None` but a later
+    /// field was not.
+    OptionalNotAtEnd {
+        field_index: usize,
+    },
+    /// `Some(None)` serializes the same as `None`.
+    NestedNone,
+    /// Values nested deeper than the deserializer allows, starting at
+    /// `pos`.
+    RecursionLimitExceeded {
+        pos: usize,
+    },
+    TrailingBytes {
+        pos: usize,
+        remaining: usize,
+    },
 }
 
 impl ser::Error for Error {
@@ -45,17 +117,91 @@ impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Message(msg) => formatter.write_str(msg),
-            Error::UnknownType(i) => formatter.write_fmt(format_args!("unknown data type {}", i)),
+            Error::Eof { pos } => {
+                formatter.write_fmt(format_args!("unexpected end of input at byte {}", pos))
+            }
+            Error::UnexpectedEof { needed, got, pos } => formatter.write_fmt(format_args!(
+                "unexpected end of input at byte {}, needed {} bytes but got {}",
+                pos, needed, got
+            )),
+            Error::UnknownType {
+                value,
+                pos: Some(pos),
+            } => formatter.write_fmt(format_args!("unknown data type {} at byte {}", value, pos)),
+            Error::UnknownType { value, pos: None } => {
+                formatter.write_fmt(format_args!("unknown data type {}", value))
+            }
+            #[cfg(feature = "std")]
             Error::ReadError(e) => formatter.write_str(&e.to_string()),
+            #[cfg(feature = "json")]
+            Error::Json(e) => formatter.write_str(&e.to_string()),
+            Error::BadValue { pos } => {
+                formatter.write_fmt(format_args!("bad value at byte {}", pos))
+            }
+            Error::Unexpected { want, got, pos } => match want {
+                Some(want) => formatter.write_fmt(format_args!(
+                    "expected {:?}, got {:?} at byte {}",
+                    want, got, pos
+                )),
+                None => formatter.write_fmt(format_args!("unexpected {:?} at byte {}", got, pos)),
+            },
+            Error::Expected { want, got, pos } => formatter.write_fmt(format_args!(
+                "expected {:?}, got {:?} at byte {}",
+                want, got, pos
+            )),
+            Error::InvalidUtf8 { pos, .. } => {
+                formatter.write_fmt(format_args!("invalid UTF-8 string at byte {}", pos))
+            }
+            Error::InvalidLength { len, pos } => {
+                formatter.write_fmt(format_args!("invalid length {} at byte {}", len, pos))
+            }
+            Error::LengthLimitExceeded { len, limit, pos } => formatter.write_fmt(format_args!(
+                "length {} at byte {} exceeds the limit of {}",
+                len, pos, limit
+            )),
+            Error::UnsupportedType(name) => formatter.write_fmt(format_args!(
+                "{} is not supported by KRDS, the largest integer is a Long (use i64)",
+                name
+            )),
+            Error::UnknownField { name, pos } => {
+                formatter.write_fmt(format_args!("unknown field {} at byte {}", name, pos))
+            }
+            Error::SequenceTooLong { len } => formatter.write_fmt(format_args!(
+                "sequence of {} elements is too long, the most is {}",
+                len,
+                i32::MAX
+            )),
+            Error::DuplicateKey { pos } => {
+                formatter.write_fmt(format_args!("duplicate map key at byte {}", pos))
+            }
+            Error::OptionalNotAtEnd { field_index } => formatter.write_fmt(format_args!(
+                "tuple struct field {} is or ends with None but a later field is not",
+                field_index
+            )),
+            Error::NestedNone => formatter.write_str("Some(None) cannot be serialized"),
+            Error::RecursionLimitExceeded { pos } => {
+                formatter.write_fmt(format_args!("recursion limit exceeded at byte {}", pos))
+            }
+            Error::TrailingBytes { pos, remaining } => {
+                formatter.write_fmt(format_args!("{} trailing bytes at byte {}", remaining, pos))
+            }
             _ => formatter.write_fmt(format_args!("{:?}", self)),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
         Self::ReadError(value)
     }
 }
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
@@ -0,0 +1,204 @@
+//! Pull parser and writer over the values of a KRDS file, for when
+//! building the whole struct tree is not needed (e.g. counting notes in
+//! a large annotation file or bumping every timestamp.)
+
+use serde::Serializer as _;
+
+use crate::de::Deserializer;
+use crate::error::Result;
+use crate::ser::{Serializer, Write};
+use crate::DataType;
+
+/// A single scalar value, strings are borrowed from the input.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KrdsScalar<'de> {
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    String(&'de str),
+    Double(f64),
+    Short(i16),
+    Float(f32),
+    Byte(i8),
+    Char(char),
+}
+
+/// Something read from a KRDS file by [`KrdsEventReader`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KrdsEvent<'de> {
+    /// Number of top-level values in the document. Other length
+    /// prefixes cannot be told apart from values and are reported as
+    /// [`KrdsScalar::Int`].
+    SeqStart(usize),
+    /// A `FieldBegin` and its name.
+    FieldStart(&'de str),
+    FieldEnd,
+    Scalar(KrdsScalar<'de>),
+}
+
+/// Iterator over the [`KrdsEvent`]s of a KRDS file. Iteration stops
+/// after the first error.
+#[derive(Debug)]
+pub struct KrdsEventReader<'de> {
+    de: Deserializer<'de>,
+    started: bool,
+    done: bool,
+}
+
+impl<'de> KrdsEventReader<'de> {
+    /// Check the magic bytes of a KRDS file and read the events that
+    /// follow them.
+    pub fn new(b: &'de [u8]) -> Result<Self> {
+        Ok(KrdsEventReader {
+            de: Deserializer::with_magic(b)?,
+            started: false,
+            done: false,
+        })
+    }
+
+    fn next_event(&mut self) -> Result<KrdsEvent<'de>> {
+        Ok(match self.de.next_datatype()? {
+            datatype if datatype.is_scalar() => match self.de.parse_scalar(datatype)? {
+                KrdsScalar::Int(len) if !self.started && len >= 0 => {
+                    KrdsEvent::SeqStart(len as usize)
+                }
+                scalar => KrdsEvent::Scalar(scalar),
+            },
+            DataType::FieldBegin => KrdsEvent::FieldStart(self.de.parse_string()?),
+            _ => KrdsEvent::FieldEnd,
+        })
+    }
+}
+
+impl<'de> Iterator for KrdsEventReader<'de> {
+    type Item = Result<KrdsEvent<'de>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.de.end().is_ok() {
+            return None;
+        }
+        let event = self.next_event();
+        self.started = true;
+        self.done = event.is_err();
+        Some(event)
+    }
+}
+
+/// Writes [`KrdsEvent`]s as KRDS bytes. Events read by a
+/// [`KrdsEventReader`] and written back unchanged produce the original
+/// file.
+pub struct KrdsEventWriter<W: Write> {
+    ser: Serializer<W>,
+}
+
+impl<W: Write> KrdsEventWriter<W> {
+    /// Write the KRDS magic, the events follow it.
+    pub fn new(writer: W) -> Result<Self> {
+        let mut ser = Serializer::from_writer(writer);
+        ser.output.write_all(crate::MAGIC)?;
+        Ok(KrdsEventWriter { ser })
+    }
+
+    /// Events are written as they come, nothing checks that they make
+    /// up a valid document (e.g. that every `FieldStart` has a
+    /// `FieldEnd`.)
+    pub fn write(&mut self, event: &KrdsEvent) -> Result<()> {
+        let ser = &mut self.ser;
+        match *event {
+            KrdsEvent::SeqStart(len) => ser.write_len(len),
+            KrdsEvent::FieldStart(name) => {
+                ser.write_dtype(DataType::FieldBegin)?;
+                ser.write_str(name)
+            }
+            KrdsEvent::FieldEnd => ser.write_dtype(DataType::FieldEnd),
+            KrdsEvent::Scalar(scalar) => match scalar {
+                KrdsScalar::Bool(v) => ser.serialize_bool(v),
+                KrdsScalar::Int(v) => ser.serialize_i32(v),
+                KrdsScalar::Long(v) => ser.serialize_i64(v),
+                KrdsScalar::String(v) => ser.serialize_str(v),
+                KrdsScalar::Double(v) => ser.serialize_f64(v),
+                KrdsScalar::Short(v) => ser.serialize_i16(v),
+                KrdsScalar::Float(v) => ser.serialize_f32(v),
+                KrdsScalar::Byte(v) => ser.serialize_i8(v),
+                KrdsScalar::Char(v) => ser.serialize_char(v),
+            },
+        }
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.ser.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test::*;
+    use crate::{read_header, Error, KrdsValue};
+
+    fn count_objects(value: &KrdsValue) -> usize {
+        match value {
+            KrdsValue::Object { fields, .. } => 1 + fields.iter().map(count_objects).sum::<usize>(),
+            KrdsValue::List(values) => values.iter().map(count_objects).sum(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn count_field_starts() {
+        for file in [
+            PDFANNOT_YJR,
+            PDFANNOT_YJF,
+            BOOK_HL_NOTE_AZW3R,
+            BOOK_HL_NOTE_AZW3F,
+        ] {
+            let events = KrdsEventReader::new(file)
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(
+                events[0],
+                KrdsEvent::SeqStart(read_header(file).unwrap().fields.len())
+            );
+            assert_eq!(
+                events
+                    .iter()
+                    .filter(|e| matches!(e, KrdsEvent::FieldStart(_)))
+                    .count(),
+                KrdsValue::from_document(file)
+                    .unwrap()
+                    .iter()
+                    .map(count_objects)
+                    .sum::<usize>()
+            );
+        }
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut reader = KrdsEventReader::new(&PDFANNOT_YJR[..PDFANNOT_YJR.len() - 3]).unwrap();
+        assert!(matches!(
+            reader.by_ref().find(Result::is_err),
+            Some(Err(Error::UnexpectedEof { .. }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn write_round_trip() {
+        for file in [
+            PDFANNOT_YJR,
+            PDFANNOT_YJF,
+            BOOK_HL_NOTE_AZW3R,
+            BOOK_HL_NOTE_AZW3F,
+        ] {
+            let mut writer = KrdsEventWriter::new(Vec::new()).unwrap();
+            for event in KrdsEventReader::new(file).unwrap() {
+                writer.write(&event.unwrap()).unwrap();
+            }
+            assert_eq!(writer.into_inner(), file);
+        }
+    }
+}
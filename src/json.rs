@@ -0,0 +1,215 @@
+//! Conversion between KRDS data and JSON, for inspecting and editing
+//! Kindle files with ordinary JSON tools.
+//!
+//! Struct fields keep their KRDS field names (as given by
+//! `#[serde(rename = "...")]`) and maps keep their on-disk order.
+//! Names of newtype wrappers (e.g. `saved.avl.interval.tree`) are not
+//! part of the JSON output, they are restored from the target type
+//! when converting back.
+//!
+//! Files without a matching type can be converted with
+//! [`krds_to_json_bytes`] and [`json_to_krds_bytes`], which go through
+//! a [`KrdsValue`] tree. JSON numbers cannot tell an `Int` from a
+//! `Long`, `Short` or `Byte`, so every value is tagged with its data
+//! type as an object with a single key, and objects list their name and
+//! values:
+//!
+//! ```json
+//! [
+//!   {"Int": 1},
+//!   {"Object": {"name": "language.store", "fields": [{"String": "en-US"}, {"Int": 4}]}}
+//! ]
+//! ```
+//!
+//! JSON has no NaN or infinity, files containing those cannot be
+//! converted.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::value::KrdsValue;
+
+/// Convert a value into a JSON value.
+pub fn to_json<T>(value: &T) -> Result<serde_json::Value>
+where
+    T: Serialize,
+{
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Convert a JSON value back into a typed value.
+pub fn from_json<T>(value: serde_json::Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Parse a KRDS file and convert it into a JSON value.
+pub fn krds_to_json<T>(bytes: &[u8]) -> Result<serde_json::Value>
+where
+    T: DeserializeOwned + Serialize,
+{
+    to_json(&crate::from_bytes::<T>(bytes)?)
+}
+
+/// Convert a JSON value into a KRDS file.
+pub fn json_to_krds<T>(value: serde_json::Value) -> Result<Vec<u8>>
+where
+    T: DeserializeOwned + Serialize,
+{
+    crate::to_bytes(&from_json::<T>(value)?)
+}
+
+/// JSON form of a [`KrdsValue`], see the module documentation. The
+/// document itself is a plain array.
+#[derive(Serialize, Deserialize)]
+enum Tagged {
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    String(String),
+    Double(f64),
+    Short(i16),
+    Float(f32),
+    Byte(i8),
+    Char(char),
+    Object { name: String, fields: Vec<Tagged> },
+    List(Vec<Tagged>),
+}
+
+impl From<KrdsValue> for Tagged {
+    fn from(value: KrdsValue) -> Self {
+        match value {
+            KrdsValue::Bool(v) => Tagged::Bool(v),
+            KrdsValue::Int(v) => Tagged::Int(v),
+            KrdsValue::Long(v) => Tagged::Long(v),
+            KrdsValue::String(v) => Tagged::String(v),
+            KrdsValue::Double(v) => Tagged::Double(v),
+            KrdsValue::Short(v) => Tagged::Short(v),
+            KrdsValue::Float(v) => Tagged::Float(v),
+            KrdsValue::Byte(v) => Tagged::Byte(v),
+            KrdsValue::Char(v) => Tagged::Char(v),
+            KrdsValue::Object { name, fields } => Tagged::Object {
+                name,
+                fields: fields.into_iter().map(Tagged::from).collect(),
+            },
+            KrdsValue::List(values) => Tagged::List(values.into_iter().map(Tagged::from).collect()),
+        }
+    }
+}
+
+impl From<Tagged> for KrdsValue {
+    fn from(value: Tagged) -> Self {
+        match value {
+            Tagged::Bool(v) => KrdsValue::Bool(v),
+            Tagged::Int(v) => KrdsValue::Int(v),
+            Tagged::Long(v) => KrdsValue::Long(v),
+            Tagged::String(v) => KrdsValue::String(v),
+            Tagged::Double(v) => KrdsValue::Double(v),
+            Tagged::Short(v) => KrdsValue::Short(v),
+            Tagged::Float(v) => KrdsValue::Float(v),
+            Tagged::Byte(v) => KrdsValue::Byte(v),
+            Tagged::Char(v) => KrdsValue::Char(v),
+            Tagged::Object { name, fields } => KrdsValue::Object {
+                name,
+                fields: fields.into_iter().map(KrdsValue::from).collect(),
+            },
+            Tagged::List(values) => {
+                KrdsValue::List(values.into_iter().map(KrdsValue::from).collect())
+            }
+        }
+    }
+}
+
+/// Convert any KRDS file into tagged JSON, without knowing its type.
+pub fn krds_to_json_bytes(input: &[u8]) -> Result<Vec<u8>> {
+    let values: Vec<Tagged> = KrdsValue::from_document(input)?
+        .into_iter()
+        .map(Tagged::from)
+        .collect();
+    Ok(serde_json::to_vec_pretty(&values)?)
+}
+
+/// Convert tagged JSON from [`krds_to_json_bytes`] back into a KRDS
+/// file.
+pub fn json_to_krds_bytes(input: &[u8]) -> Result<Vec<u8>> {
+    let values: Vec<Tagged> = serde_json::from_slice(input)?;
+    crate::to_bytes(&KrdsValue::List(
+        values.into_iter().map(KrdsValue::from).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use kindle_formats::krds::*;
+
+    use crate::test::*;
+
+    #[test]
+    fn pdfannot_yjr_json() {
+        let json = to_json(&pdfannot_yjr()).unwrap();
+        let fields: Vec<_> = json.as_object().unwrap().keys().collect();
+        assert_eq!(
+            fields,
+            [
+                "next.in.series.info.data",
+                "annotation.cache.object",
+                "language.store",
+                "ReaderMetrics"
+            ]
+        );
+        assert!(json["annotation.cache.object"]["10"].is_array());
+    }
+
+    #[test]
+    fn pdfannot_yjf_json() {
+        let json = to_json(&pdfannot_yjf()).unwrap();
+        assert_eq!(from_json::<TimerDataFile>(json).unwrap(), pdfannot_yjf());
+    }
+
+    #[test]
+    fn pdfannot_yjf_krds_json() {
+        let json = krds_to_json::<TimerDataFile>(PDFANNOT_YJF).unwrap();
+        assert_eq!(json_to_krds::<TimerDataFile>(json).unwrap(), PDFANNOT_YJF);
+    }
+
+    #[test]
+    fn untyped_json_round_trip() {
+        for file in [
+            PDFANNOT_YJR,
+            PDFANNOT_YJF,
+            BOOK_HL_NOTE_AZW3R,
+            BOOK_HL_NOTE_AZW3F,
+        ] {
+            let json = krds_to_json_bytes(file).unwrap();
+            assert_eq!(json_to_krds_bytes(&json).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn untyped_json_edit() {
+        let mut json: serde_json::Value =
+            serde_json::from_slice(&krds_to_json_bytes(PDFANNOT_YJR).unwrap()).unwrap();
+        assert_eq!(json[0], serde_json::json!({"Int": 4}));
+        assert_eq!(
+            json[3],
+            serde_json::json!({"Object": {
+                "name": "language.store",
+                "fields": [{"String": "en-US"}, {"Int": 4}]
+            }})
+        );
+
+        json[3]["Object"]["fields"][0]["String"] = "fr-FR".into();
+        let bytes = json_to_krds_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(
+            crate::from_bytes::<ReaderDataFile>(&bytes)
+                .unwrap()
+                .language_store,
+            Some(LanguageStore("fr-FR".to_string(), 4))
+        );
+    }
+}
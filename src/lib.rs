@@ -2,20 +2,41 @@
 //! format (used by Kindle e-readers to store user reading data.)
 //!
 //! Warning, some types are fragile, for example Tuple Structs cannot
-//! contain optionals anywhere except at the end. They will fail to
-//! deserialize if the optional is none if this rule is not followed.
+//! contain optionals anywhere except at the end. Serializing one where
+//! a `None` is followed by a value fails with `OptionalNotAtEnd`.
 //! More stable implementations may be created as needs arise and I
 //! understand serde more.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod de;
+pub mod diff;
 pub mod error;
+pub mod event;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod ser;
+pub mod value;
 
-pub use de::{from_bytes, Deserializer};
+pub use de::{
+    from_bytes, from_bytes_partial, from_bytes_raw, from_bytes_scan, from_bytes_strict,
+    from_document, read_header, sniff_file_type, Deserializer, FieldNameMap, FileKind, Header,
+    OwnedDeserializer,
+};
+#[cfg(feature = "std")]
+pub use de::{from_file, from_reader};
+pub use diff::{diff, DiffEntry};
 pub use error::{Error, Result};
-pub use ser::{to_bytes, Serializer};
+pub use event::{KrdsEvent, KrdsEventReader, KrdsEventWriter, KrdsScalar};
+#[cfg(feature = "std")]
+pub use ser::to_file;
+pub use ser::{to_bytes, to_bytes_raw, to_bytes_with_capacity, to_writer, Serializer};
+pub use value::{CharArray, IntVariant, KrdsValue};
 
 #[cfg(test)]
+#[allow(clippy::zero_prefixed_literal)]
 mod test;
 
 /// All KRDS files start with the following magic bytes (magic number
@@ -23,8 +44,48 @@ mod test;
 pub(crate) const MAGIC: &[u8; 17] =
     b"\x00\x00\x00\x00\x00\x1A\xB1\x26\x02\x00\x00\x00\x00\x00\x00\x00\x01";
 
+/// Magic number at the start of the [`MAGIC`] bytes.
+pub(crate) const SIGNATURE: &[u8; 9] = b"\x00\x00\x00\x00\x00\x1A\xB1\x26\x02";
+
+/// Format version following the [`SIGNATURE`], the only one known.
+pub(crate) const VERSION: u64 = 1;
+
+/// Length prefix of a non-empty string.
+///
+/// Strings are encoded the same way everywhere they appear: values
+/// (including unit variants and map keys) after a `String` data type,
+/// and names following a `FieldBegin` (struct fields, newtypes and
+/// variants) without one. A marker byte comes first, [`EMPTY_STRING`]
+/// for an empty string, otherwise 0 followed by the byte length as a
+/// big endian `StringLen` and the UTF-8 bytes.
+pub(crate) type StringLen = u16;
+
+/// Marker byte of an empty string, which has no length or contents.
+pub(crate) const EMPTY_STRING: u8 = 1;
+
 /// Map of data type specifiers to the name of the types they
 /// represent.
+///
+/// Rust types are written as follows:
+///
+/// | Rust type                        | Data type                          |
+/// |----------------------------------|------------------------------------|
+/// | `bool`                           | `Boolean`                          |
+/// | `i8`, `u8`                       | `Byte`                             |
+/// | `i16`, `u16`                     | `Short`                            |
+/// | `i32`, `u32`                     | `Int`                              |
+/// | `i64`, `u64`                     | `Long`                             |
+/// | `f32`                            | `Float`                            |
+/// | `f64`                            | `Double`                           |
+/// | `char`                           | `Char` (one UTF-16 unit)           |
+/// | `String`, `&str`, unit variants  | `String`                           |
+/// | sequences, maps                  | `Int` length, then the elements    |
+/// | tuples, `[T; N]`, tuple structs  | the elements                       |
+/// | structs, newtypes, other variants| `FieldBegin` name ... `FieldEnd`   |
+/// | `Option`                         | the inner value, `None` is omitted |
+///
+/// Unsigned integers are written with the bits of the signed type of
+/// the same width, `u128` and `i128` are not supported.
 #[repr(i8)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DataType {
@@ -41,10 +102,30 @@ pub enum DataType {
     FieldEnd = -1,
 }
 
+impl DataType {
+    /// Number of bytes following the data type byte for fixed size
+    /// types, `None` for `String` and the field markers.
+    pub fn byte_width(&self) -> Option<usize> {
+        match self {
+            Self::Boolean | Self::Byte => Some(1),
+            Self::Short | Self::Char => Some(2),
+            Self::Int | Self::Float => Some(4),
+            Self::Long | Self::Double => Some(8),
+            Self::String | Self::FieldBegin | Self::FieldEnd => None,
+        }
+    }
+
+    /// Whether the data type is followed by a single value, as opposed
+    /// to the `FieldBegin` and `FieldEnd` markers.
+    pub fn is_scalar(&self) -> bool {
+        !matches!(self, Self::FieldBegin | Self::FieldEnd)
+    }
+}
+
 impl TryFrom<i8> for DataType {
     type Error = Error;
 
-    fn try_from(value: i8) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: i8) -> core::result::Result<Self, Self::Error> {
         Ok(match value {
             0 => Self::Boolean,
             1 => Self::Int,
@@ -58,7 +139,7 @@ impl TryFrom<i8> for DataType {
             -2 => Self::FieldBegin,
             -1 => Self::FieldEnd,
             _ => {
-                return Err(Self::Error::UnknownType(value));
+                return Err(Self::Error::UnknownType { value, pos: None });
             }
         })
     }
@@ -67,7 +148,7 @@ impl TryFrom<i8> for DataType {
 impl TryFrom<u8> for DataType {
     type Error = Error;
 
-    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: u8) -> core::result::Result<Self, Self::Error> {
         Self::try_from(value as i8)
     }
 }
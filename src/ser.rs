@@ -1,4 +1,7 @@
-use std::io::Write;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::Path;
 
 use serde::{
     ser::{self, Impossible, SerializeSeq},
@@ -9,49 +12,183 @@ use crate::error::{Error, Result};
 
 use crate::DataType;
 
-pub struct Serializer {
-    pub output: Vec<u8>,
+/// Output of a [`Serializer`]. With the `std` feature any
+/// [`std::io::Write`] can be used, without it only a `Vec<u8>`.
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        std::io::Write::write_all(self, buf)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct Serializer<W: Write = Vec<u8>> {
+    pub output: W,
+    /// How the last value written ends, see [`TupleStructSerializer`].
+    last: Last,
+}
+
+/// How the last value written by a [`Serializer`] ends.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Last {
+    #[default]
+    Value,
+    /// The value was `None`, which is not written at all.
+    None,
+    /// A sequence, tuple (struct) or map whose last element is or ends
+    /// with `None`.
+    TrailingNone,
+}
+
+impl Serializer {
+    /// Serializer writing into an empty buffer, without the KRDS magic
+    /// (see [`with_magic`](Self::with_magic) for a whole file.)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serializer writing a whole file, the buffer starts with the KRDS
+    /// magic.
+    pub fn with_magic() -> Self {
+        Serializer::from_writer(crate::MAGIC.to_vec())
+    }
+
+    /// Serializer writing into a buffer with room for `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Serializer::from_writer(Vec::with_capacity(capacity))
+    }
+
+    /// Get the bytes written so far.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.output
+    }
 }
 
 pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
-    let output = Vec::from(crate::MAGIC.clone().as_slice());
-    let mut serializer = Serializer { output };
+    to_bytes_with_capacity(value, 0)
+}
+
+/// Like [`to_bytes`] but the output starts with room for `capacity`
+/// bytes, which avoids growing it repeatedly when the rough size is
+/// known (e.g. when rewriting a file that was just read.)
+pub fn to_bytes_with_capacity<T>(value: &T, capacity: usize) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::with_capacity(capacity);
+    serializer.output.write_all(crate::MAGIC)?;
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    Ok(serializer.into_bytes())
 }
 
-impl Serializer {
-    fn write_str(&mut self, string: &str) -> Result<()> {
+/// Serialize a value without the KRDS magic, for embedding in another
+/// container. The result is not a valid file on its own.
+pub fn to_bytes_raw<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::new();
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_bytes())
+}
+
+/// Serialize a value (prefixed with the KRDS magic) straight into a
+/// writer without building an intermediate buffer.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: Serialize,
+{
+    let mut serializer = Serializer::from_writer(writer);
+    serializer.output.write_all(crate::MAGIC)?;
+    value.serialize(&mut serializer)
+}
+
+/// Serialize a value and write it to a KRDS file at `path`, replacing
+/// the file if it already exists.
+#[cfg(feature = "std")]
+pub fn to_file<P, T>(path: P, value: &T) -> Result<()>
+where
+    P: AsRef<Path>,
+    T: Serialize,
+{
+    std::fs::write(path, to_bytes(value)?)?;
+    Ok(())
+}
+
+impl<W: Write> Serializer<W> {
+    /// Serializer writing to any [`Write`], without the KRDS magic.
+    pub fn from_writer(output: W) -> Self {
+        Serializer {
+            output,
+            last: Last::Value,
+        }
+    }
+
+    /// See [`StringLen`](crate::StringLen), longer strings cannot be
+    /// represented.
+    pub(crate) fn write_str(&mut self, string: &str) -> Result<()> {
         if string.is_empty() {
-            self.output.write_all(&[1])?;
+            self.output.write_all(&[crate::EMPTY_STRING])?;
         } else {
+            let len = crate::StringLen::try_from(string.len())
+                .map_err(|_| Error::StringTooLong { len: string.len() })?;
             self.output.write_all(&[0])?;
-            self.output
-                .write_all(&(string.len() as u16).to_be_bytes())?;
+            self.output.write_all(&len.to_be_bytes())?;
             self.output.write_all(string.as_bytes())?;
         }
         Ok(())
     }
 
-    fn write_dtype(&mut self, dtype: DataType) -> Result<()> {
+    /// Length prefix of a sequence, map or struct. It is a Java `int`,
+    /// so longer ones cannot be represented.
+    pub(crate) fn write_len(&mut self, len: usize) -> Result<()> {
+        let len = i32::try_from(len).map_err(|_| Error::SequenceTooLong { len })?;
+        ser::Serializer::serialize_i32(self, len)
+    }
+
+    pub(crate) fn write_dtype(&mut self, dtype: DataType) -> Result<()> {
+        self.last = Last::Value;
         self.output.write_all(&[dtype as u8])?;
         Ok(())
     }
+
+    /// Called once the elements of a compound value without a closing
+    /// `FieldEnd` are written: a `None` at the end is now at the end of
+    /// the whole value.
+    fn end_compound(&mut self) {
+        if self.last == Last::None {
+            self.last = Last::TrailingNone;
+        }
+    }
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     type Ok = ();
 
     type Error = Error;
 
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
+    type SerializeSeq = SeqSerializer<'a, W>;
+    type SerializeTuple = TupleStructSerializer<'a, W>;
+    type SerializeTupleStruct = TupleStructSerializer<'a, W>;
     type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
+    type SerializeMap = MapSerializer<'a, W>;
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
 
@@ -100,6 +237,14 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_i64(v as i64)
     }
 
+    fn serialize_i128(self, _v: i128) -> Result<()> {
+        Err(Error::UnsupportedType("i128"))
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<()> {
+        Err(Error::UnsupportedType("u128"))
+    }
+
     fn serialize_f32(self, v: f32) -> Result<()> {
         self.write_dtype(DataType::Float)?;
         self.output.write_all(&v.to_be_bytes())?;
@@ -112,9 +257,16 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
+    /// Chars are stored as a single UTF-16 code unit (like a Java
+    /// `char`), so chars outside the basic multilingual plane, which
+    /// would need a surrogate pair, cannot be serialized.
     fn serialize_char(self, v: char) -> Result<()> {
+        let mut units = [0; 2];
+        let &mut [unit] = v.encode_utf16(&mut units) else {
+            return Err(Error::UnencodableChar(v));
+        };
         self.write_dtype(DataType::Char)?;
-        self.output.write_all(&[v as u8])?;
+        self.output.write_all(&unit.to_be_bytes())?;
         Ok(())
     }
 
@@ -132,17 +284,26 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_none(self) -> Result<()> {
+        self.last = Last::None;
         Ok(())
     }
 
+    /// `None` is not written at all, so `Some(None)` could not be told
+    /// apart from `None` when deserializing.
     fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        self.last = Last::Value;
+        value.serialize(&mut *self)?;
+        if self.last == Last::None {
+            return Err(Error::NestedNone);
+        }
+        Ok(())
     }
 
     fn serialize_unit(self) -> Result<()> {
+        self.last = Last::Value;
         self.output.write_all(&[-1i8 as u8])?;
         Ok(())
     }
@@ -164,8 +325,17 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
+        if name == crate::value::NAME_TOKEN {
+            return self.write_str(&value.serialize(MapKeySerializer)?);
+        }
+        if name == crate::value::INT_VARIANT_TOKEN {
+            return self.serialize_i32(value.serialize(VariantIndexSerializer)?);
+        }
         self.write_dtype(DataType::FieldBegin)?;
-        self.write_str(name)?;
+        // Objects from a `KrdsValue` write their own (non static) name.
+        if name != crate::value::OBJECT_TOKEN {
+            self.write_str(name)?;
+        }
         value.serialize(&mut *self)?;
         self.write_dtype(DataType::FieldEnd)
     }
@@ -187,13 +357,31 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
+    /// Elements of a sequence with unknown length are buffered until
+    /// the length prefix can be written.
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.serialize_i32(len.unwrap() as i32)?;
-        Ok(self)
+        let elements = match len {
+            Some(len) => {
+                self.write_len(len)?;
+                None
+            }
+            None => Some((Serializer::new(), 0)),
+        };
+        Ok(SeqSerializer {
+            ser: self,
+            elements,
+        })
     }
 
-    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-        self.serialize_seq(Some(len))
+    /// Tuples (and fixed size arrays) have a length known to both
+    /// sides, so they are written like tuple structs: just the
+    /// elements, without a length or `FieldEnd`.
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Ok(TupleStructSerializer {
+            ser: self,
+            index: 0,
+            first_none: None,
+        })
     }
 
     fn serialize_tuple_struct(
@@ -201,7 +389,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(self)
+        Ok(TupleStructSerializer {
+            ser: self,
+            index: 0,
+            first_none: None,
+        })
     }
 
     fn serialize_tuple_variant(
@@ -216,13 +408,21 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(self)
     }
 
+    /// Entries of a map with unknown length are buffered like the
+    /// elements of a sequence.
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.serialize_i32(len.unwrap() as i32)?;
-        Ok(self)
+        let entries = match len {
+            Some(len) => {
+                self.write_len(len)?;
+                None
+            }
+            None => Some((Serializer::new(), 0)),
+        };
+        Ok(MapSerializer { ser: self, entries })
     }
 
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_i32(len as i32)?;
+        self.write_len(len)?;
         Ok(self)
     }
 
@@ -230,15 +430,25 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.serialize_i32(len as i32)?;
+        self.write_dtype(DataType::FieldBegin)?;
+        self.write_str(variant)?;
+        self.write_len(len)?;
         Ok(self)
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+/// Serializer for sequences, see `serialize_seq`.
+pub struct SeqSerializer<'a, W: Write> {
+    ser: &'a mut Serializer<W>,
+    /// Buffered elements (and their count) of a sequence with unknown
+    /// length.
+    elements: Option<(Serializer, usize)>,
+}
+
+impl<'a, W: Write> ser::SerializeSeq for SeqSerializer<'a, W> {
     type Ok = ();
     type Error = Error;
 
@@ -246,47 +456,92 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match &mut self.elements {
+            Some((elements, count)) => {
+                *count += 1;
+                elements.last = Last::Value;
+                value.serialize(elements)
+            }
+            None => {
+                self.ser.last = Last::Value;
+                value.serialize(&mut *self.ser)
+            }
+        }
     }
 
     fn end(self) -> Result<()> {
+        if let Some((elements, count)) = self.elements {
+            self.ser.write_len(count)?;
+            self.ser.output.write_all(&elements.output)?;
+            self.ser.last = elements.last;
+        }
+        self.ser.end_compound();
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+/// Serializer for tuples and tuple structs, see `serialize_tuple_struct`.
+///
+/// Tuple struct fields are not delimited and `None` is not written at
+/// all, so an absent optional can only be told apart from the fields
+/// after it if all of those are absent too. The same goes for a field
+/// that itself ends with an absent optional (e.g. a nested tuple
+/// struct), which is only delimited if nothing but `None` follows it.
+/// Anything else is rejected with [`Error::OptionalNotAtEnd`].
+pub struct TupleStructSerializer<'a, W: Write> {
+    ser: &'a mut Serializer<W>,
+    index: usize,
+    /// First field that is or ends with `None`, nothing may be written
+    /// after it.
+    first_none: Option<usize>,
+}
+
+impl<'a, W: Write> ser::SerializeTupleStruct for TupleStructSerializer<'a, W> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.ser.last = Last::Value;
+        value.serialize(&mut *self.ser)?;
+        match (self.ser.last, self.first_none) {
+            (Last::None, _) => {
+                self.first_none.get_or_insert(self.index);
+            }
+            (_, Some(field_index)) => return Err(Error::OptionalNotAtEnd { field_index }),
+            (Last::TrailingNone, None) => self.first_none = Some(self.index),
+            (Last::Value, None) => {}
+        }
+        self.index += 1;
+        Ok(())
     }
 
+    /// Passes a `None` ending the last field on to the parent.
     fn end(self) -> Result<()> {
-        self.write_dtype(DataType::FieldEnd)
+        self.ser.end_compound();
+        Ok(())
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl<'a, W: Write> ser::SerializeTuple for TupleStructSerializer<'a, W> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        ser::SerializeTupleStruct::serialize_field(self, value)
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        ser::SerializeTupleStruct::end(self)
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -302,7 +557,14 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+/// Serializer for maps, see `serialize_map`.
+pub struct MapSerializer<'a, W: Write> {
+    ser: &'a mut Serializer<W>,
+    /// Buffered entries (and their count) of a map with unknown length.
+    entries: Option<(Serializer, usize)>,
+}
+
+impl<'a, W: Write> ser::SerializeMap for MapSerializer<'a, W> {
     type Ok = ();
     type Error = Error;
 
@@ -310,22 +572,41 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        match &mut self.entries {
+            Some((entries, _)) => key.serialize(entries),
+            None => key.serialize(&mut *self.ser),
+        }
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        match &mut self.entries {
+            Some((entries, count)) => {
+                *count += 1;
+                entries.last = Last::Value;
+                value.serialize(entries)
+            }
+            None => {
+                self.ser.last = Last::Value;
+                value.serialize(&mut *self.ser)
+            }
+        }
     }
 
     fn end(self) -> Result<()> {
+        if let Some((entries, count)) = self.entries {
+            self.ser.write_len(count)?;
+            self.ser.output.write_all(&entries.output)?;
+            self.ser.last = entries.last;
+        }
+        self.ser.end_compound();
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -344,7 +625,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -359,10 +640,180 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        self.write_dtype(DataType::FieldEnd)
+    }
+}
+
+/// Gets the index of a unit variant, for [`IntVariant`](crate::value::IntVariant).
+struct VariantIndexSerializer;
+
+fn not_unit_variant_err() -> Error {
+    Error::Message("IntVariant only supports unit variants".to_string())
+}
+
+impl serde::Serializer for VariantIndexSerializer {
+    type Ok = i32;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<i32, Error>;
+    type SerializeTuple = Impossible<i32, Error>;
+    type SerializeTupleStruct = Impossible<i32, Error>;
+    type SerializeTupleVariant = Impossible<i32, Error>;
+    type SerializeMap = Impossible<i32, Error>;
+    type SerializeStruct = Impossible<i32, Error>;
+    type SerializeStructVariant = Impossible<i32, Error>;
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<i32> {
+        i32::try_from(variant_index).map_err(|_| not_unit_variant_err())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_none(self) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<i32>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_unit(self) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<i32> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<i32>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<i32>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(not_unit_variant_err())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_unit_variant_err())
     }
 }
 
+/// Gets the name of a [`KrdsValue`](crate::KrdsValue) object. Only
+/// strings are names, anything else is an error.
 struct MapKeySerializer;
 
 fn bad_key_err() -> Error {
@@ -381,80 +832,73 @@ impl serde::Serializer for MapKeySerializer {
     type SerializeStruct = Impossible<String, Error>;
     type SerializeStructVariant = Impossible<String, Error>;
 
-    #[inline]
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        variant: &'static str,
+        _variant: &'static str,
     ) -> Result<String> {
-        Ok(variant.to_owned())
+        Err(bad_key_err())
     }
 
-    #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<String>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<String>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        Err(bad_key_err())
     }
 
-    fn serialize_bool(self, value: bool) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_bool(self, _value: bool) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_i8(self, value: i8) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_i8(self, _value: i8) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_i16(self, value: i16) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_i16(self, _value: i16) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_i32(self, value: i32) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_i32(self, _value: i32) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_i64(self, value: i64) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_i64(self, _value: i64) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_u8(self, value: u8) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_u8(self, _value: u8) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_u16(self, value: u16) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_u16(self, _value: u16) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_u32(self, value: u32) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_u32(self, _value: u32) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_u64(self, value: u64) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_u64(self, _value: u64) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_f32(self, value: f32) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_f32(self, _value: f32) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    fn serialize_f64(self, value: f64) -> Result<String> {
-        Ok(value.to_string())
+    fn serialize_f64(self, _value: f64) -> Result<String> {
+        Err(bad_key_err())
     }
 
-    #[inline]
-    fn serialize_char(self, value: char) -> Result<String> {
-        Ok({
-            let mut s = String::new();
-            s.push(value);
-            s
-        })
+    fn serialize_char(self, _value: char) -> Result<String> {
+        Err(bad_key_err())
     }
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<String> {
-        Ok(value.to_owned())
+        Ok(value.to_string())
     }
 
     fn serialize_bytes(self, _value: &[u8]) -> Result<String> {
@@ -539,14 +983,17 @@ impl serde::Serializer for MapKeySerializer {
 
     fn collect_str<T>(self, value: &T) -> Result<String>
     where
-        T: ?Sized + std::fmt::Display,
+        T: ?Sized + core::fmt::Display,
     {
         Ok(value.to_string())
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
+    use std::collections::BTreeMap;
+
     use super::*;
     use crate::DataType;
 
@@ -577,11 +1024,219 @@ mod test {
         assert_eq!(&to_bytes(&pdfannot_yjr()).unwrap(), PDFANNOT_YJR)
     }
 
+    #[test]
+    fn pdfannot_yjr_ser_writer() {
+        let mut output = Vec::new();
+        to_writer(&mut output, &pdfannot_yjr()).unwrap();
+        assert_eq!(output, to_bytes(&pdfannot_yjr()).unwrap())
+    }
+
+    #[test]
+    fn pdfannot_yjr_ser_with_capacity() {
+        assert_eq!(
+            &to_bytes_with_capacity(&pdfannot_yjr(), PDFANNOT_YJR.len()).unwrap(),
+            PDFANNOT_YJR
+        )
+    }
+
+    #[test]
+    fn pdfannot_yjr_ser_raw() {
+        assert_eq!(
+            to_bytes_raw(&pdfannot_yjr()).unwrap(),
+            &PDFANNOT_YJR[crate::MAGIC.len()..]
+        )
+    }
+
     #[test]
     fn pdfannot_yjf_ser() {
         assert_eq!(&to_bytes(&pdfannot_yjf()).unwrap(), PDFANNOT_YJF)
     }
 
+    #[test]
+    fn ser_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        to_file(file.path(), &pdfannot_yjf()).unwrap();
+        assert_eq!(std::fs::read(file.path()).unwrap(), PDFANNOT_YJF);
+    }
+
+    #[test]
+    fn ser_char_surrogate_pair() {
+        let mut serializer = Serializer::new();
+        assert!(matches!(
+            '\u{1F600}'.serialize(&mut serializer),
+            Err(Error::UnencodableChar('\u{1F600}'))
+        ));
+    }
+
+    #[derive(Serialize)]
+    struct OptionalInMiddle(i32, Option<i32>, Option<String>, i64);
+
+    #[test]
+    fn ser_optional_not_at_end() {
+        assert!(matches!(
+            to_bytes(&OptionalInMiddle(1, None, None, 2)),
+            Err(Error::OptionalNotAtEnd { field_index: 1 })
+        ));
+        assert!(to_bytes(&OptionalInMiddle(1, Some(2), Some(String::new()), 3)).is_ok());
+    }
+
+    #[derive(Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Inner(i32, Option<i32>);
+
+    #[derive(Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Outer(Inner, Option<i32>);
+
+    /// The `None` ending an inline tuple struct is only read back as
+    /// `None` if nothing else follows it.
+    #[test]
+    fn ser_nested_optional_not_at_end() {
+        assert!(matches!(
+            to_bytes(&Outer(Inner(1, None), Some(5))),
+            Err(Error::OptionalNotAtEnd { field_index: 0 })
+        ));
+        assert!(matches!(
+            to_bytes(&(Outer(Inner(1, None), None), 2)),
+            Err(Error::OptionalNotAtEnd { field_index: 0 })
+        ));
+        assert!(matches!(
+            to_bytes(&(BTreeMap::from([(1, None::<i32>)]), 2)),
+            Err(Error::OptionalNotAtEnd { field_index: 0 })
+        ));
+        assert!(to_bytes(&Some(Inner(1, None))).is_ok());
+        for value in [
+            Outer(Inner(1, None), None),
+            Outer(Inner(1, Some(2)), Some(5)),
+        ] {
+            assert_eq!(
+                crate::from_bytes::<Outer>(&to_bytes(&value).unwrap()).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Counter {
+        count: u128,
+    }
+
+    #[test]
+    fn ser_u128_unsupported() {
+        let error = to_bytes(&Counter { count: 1 }).unwrap_err();
+        assert!(matches!(error, Error::UnsupportedType("u128")));
+        assert!(error.to_string().contains("i64"));
+        assert!(matches!(
+            to_bytes(&-1i128),
+            Err(Error::UnsupportedType("i128"))
+        ));
+    }
+
+    struct UnknownLength(Vec<i32>);
+
+    impl Serialize for UnknownLength {
+        fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut seq = serializer.serialize_seq(None)?;
+            for n in &self.0 {
+                seq.serialize_element(n)?;
+            }
+            seq.end()
+        }
+    }
+
+    #[test]
+    fn ser_seq_unknown_length() {
+        let (bytes, data) = test_vec_int();
+        assert_eq!(ser_no_magic(UnknownLength(data)), bytes);
+        assert_eq!(
+            ser_no_magic(UnknownLength(vec![])),
+            test_num(0i32, DataType::Int)
+        );
+    }
+
+    struct UnknownLengthMap<K>(Vec<(K, i32)>);
+
+    impl<K: Serialize> Serialize for UnknownLengthMap<K> {
+        fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            use ser::SerializeMap;
+
+            let mut map = serializer.serialize_map(None)?;
+            for (key, value) in &self.0 {
+                map.serialize_entry(key, value)?;
+            }
+            map.end()
+        }
+    }
+
+    #[test]
+    fn ser_map_unknown_length() {
+        let entries = vec![(1, 10), (2, 20)];
+        let bytes = ser_no_magic(UnknownLengthMap(entries.clone()));
+        assert_eq!(ser_no_magic(BTreeMap::from_iter(entries.clone())), bytes);
+        assert_eq!(
+            de_no_magic::<BTreeMap<i32, i32>>(&bytes),
+            BTreeMap::from_iter(entries)
+        );
+
+        let entries = vec![("a".to_string(), 1)];
+        let bytes = ser_no_magic(UnknownLengthMap(entries.clone()));
+        assert_eq!(ser_no_magic(BTreeMap::from_iter(entries.clone())), bytes);
+        assert_eq!(
+            de_no_magic::<BTreeMap<String, i32>>(&bytes),
+            BTreeMap::from_iter(entries)
+        );
+    }
+
+    #[test]
+    fn ser_constructors() {
+        let mut serializer = Serializer::with_magic();
+        pdfannot_yjf().serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_bytes(), PDFANNOT_YJF);
+
+        let mut serializer = Serializer::new();
+        1234_i32.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_bytes(), test_num(1234_i32, DataType::Int));
+    }
+
+    #[test]
+    fn ser_sequence_too_long() {
+        let len = i32::MAX as usize + 1;
+        let mut serializer = Serializer::new();
+        assert!(matches!(
+            ser::Serializer::serialize_seq(&mut serializer, Some(len)),
+            Err(Error::SequenceTooLong { len: l }) if l == len
+        ));
+        assert!(matches!(
+            ser::Serializer::serialize_map(&mut serializer, Some(len)),
+            Err(Error::SequenceTooLong { .. })
+        ));
+        assert!(matches!(
+            ser::Serializer::serialize_struct(&mut serializer, "Big", len),
+            Err(Error::SequenceTooLong { .. })
+        ));
+        assert!(serializer.output.is_empty());
+
+        serializer.write_len(i32::MAX as usize).unwrap();
+        assert_eq!(serializer.output, test_num(i32::MAX, DataType::Int));
+    }
+
+    #[test]
+    fn ser_string_too_long() {
+        let string = "a".repeat(u16::MAX as usize);
+        assert_eq!(ser_no_magic(&string).len(), string.len() + 4);
+
+        let string = "a".repeat(u16::MAX as usize + 1);
+        let mut serializer = Serializer::new();
+        assert!(matches!(
+            string.serialize(&mut serializer),
+            Err(Error::StringTooLong { len: 65536 })
+        ));
+    }
+
     ser_num_test! {
         117_i8 => ser_i8 DataType::Byte,
         2004_i16 => ser_i16 DataType::Short,
@@ -593,8 +1248,11 @@ mod test {
 
     ser_test! {
         simple_struct_ser simple_struct,
+        unit_struct_ser unit_struct,
+        struct_variant_ser struct_variant,
         simple_newtype_ser simple_newtype,
         string_ser test_string,
+        char_ser test_char,
         empty_string_ser empty_string,
         int_vec_ser test_vec_int,
         string_vec_ser test_vec_strings,
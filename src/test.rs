@@ -1,10 +1,13 @@
 use crate::de::from_bytes;
 use crate::ser::to_bytes;
-use crate::DataType;
+use crate::{DataType, KrdsValue};
 use kindle_formats::krds::*;
 
 use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+mod options;
 
 pub const PDFANNOT_YJR: &[u8] = include_bytes!("../test_files/pdfannot.yjr");
 pub const PDFANNOT_YJF: &[u8] = include_bytes!("../test_files/pdfannot.yjf");
@@ -20,17 +23,14 @@ pub fn de_no_magic<'a, T>(input: &'a [u8]) -> T
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = crate::de::Deserializer::from_bytes(input);
-    T::deserialize(&mut deserializer).unwrap()
+    crate::de::from_bytes_raw(input).unwrap()
 }
 
 pub fn ser_no_magic<T>(input: T) -> Vec<u8>
 where
     T: Serialize,
 {
-    let mut serializer = crate::ser::Serializer { output: Vec::new() };
-    input.serialize(&mut serializer).unwrap();
-    serializer.output
+    crate::ser::to_bytes_raw(&input).unwrap()
 }
 
 pub fn handwritten_note() -> Note {
@@ -103,10 +103,28 @@ pub fn test_string() -> (Vec<u8>, String) {
     )
 }
 
+pub fn test_char() -> (Vec<u8>, char) {
+    (vec![DataType::Char as u8, 0x5b, 0x57], '字')
+}
+
 pub fn empty_string() -> (Vec<u8>, String) {
     (vec![0x03, 0x01], "".to_string())
 }
 
+/// Bytes of a string value, empty strings are written without a
+/// length.
+pub fn string_bytes(s: &str) -> Vec<u8> {
+    match s {
+        "" => empty_string().0,
+        s => [
+            &[DataType::String as u8, 0] as &[_],
+            &(s.len() as u16).to_be_bytes(),
+            s.as_bytes(),
+        ]
+        .concat(),
+    }
+}
+
 pub fn test_vec_int() -> (Vec<u8>, Vec<i32>) {
     let vec = vec![0, 1, 2, 3, 45, 44, 60];
     let mut bytes = vec![];
@@ -187,6 +205,121 @@ pub fn simple_struct() -> (Vec<u8>, SimpleStruct) {
     (test_bytes, test_orig)
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct UnitMarker;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct UnitStruct {
+    field_1: i32,
+    marker: UnitMarker,
+}
+
+pub fn unit_struct() -> (Vec<u8>, UnitStruct) {
+    let test_orig = UnitStruct {
+        field_1: 1234,
+        marker: UnitMarker,
+    };
+
+    let test_bytes = [
+        &test_num(2i32, DataType::Int) as &[_],
+        &[DataType::FieldBegin as u8, 0, 0, 7],
+        b"field_1",
+        &test_num(test_orig.field_1, DataType::Int),
+        &[
+            DataType::FieldEnd as u8,
+            DataType::FieldBegin as u8,
+            0,
+            0,
+            6,
+        ],
+        b"marker",
+        &[DataType::FieldEnd as u8, DataType::FieldEnd as u8],
+    ]
+    .concat();
+    (test_bytes, test_orig)
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum SimpleEnum {
+    First,
+    Second,
+    Third,
+}
+
+pub fn int_enum() -> (Vec<u8>, SimpleEnum) {
+    (test_num(1i32, DataType::Int), SimpleEnum::Second)
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub enum StructVariantEnum {
+    Variant { field_1: i32, field_2: String },
+}
+
+pub fn struct_variant() -> (Vec<u8>, StructVariantEnum) {
+    let (struct_bytes, data) = simple_struct();
+    let variant_name = b"Variant";
+    (
+        [
+            &[DataType::FieldBegin as u8, 0] as &[_],
+            &(variant_name.len() as u16).to_be_bytes(),
+            variant_name,
+            &struct_bytes,
+            &[DataType::FieldEnd as u8],
+        ]
+        .concat(),
+        StructVariantEnum::Variant {
+            field_1: data.field_1,
+            field_2: data.field_2,
+        },
+    )
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct OptionalTail(i32, Option<SimpleStruct>);
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct NestedOptionalTail(i32, Option<OptionalTail>);
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct OptionalTailStruct {
+    field_1: OptionalTail,
+    field_2: NestedOptionalTail,
+    field_3: OptionalTail,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ManyOptions(i32, Option<String>, Option<i64>, Option<bool>);
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ManyOptionsStruct {
+    field_1: ManyOptions,
+    field_2: ManyOptions,
+}
+
+/// Like the `page.history.store` field of `TimerDataFile`.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct HistoryStoreStruct {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    field_1: Option<Vec<i32>>,
+    field_2: Option<Vec<i32>>,
+    field_3: i32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ExtraFieldsStruct {
+    field_1: i32,
+    #[serde(flatten)]
+    extra: LinkedHashMap<String, KrdsValue>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ExtraFieldsReaderData {
+    #[serde(rename = "next.in.series.info.data")]
+    nis_info_data: String,
+    #[serde(flatten)]
+    extra: LinkedHashMap<String, KrdsValue>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct VecMapStruct {
     field_1: i32,
@@ -268,6 +401,88 @@ pub fn test_map() -> (Vec<u8>, LinkedHashMap<NoteType, String>) {
     )
 }
 
+/// Highlights stored in `bookhl+note.azw3r`.
+pub fn bookhl_note_azw3r_highlights() -> Vec<Note> {
+    vec![
+        Note::Highlight(HighlightData(
+            "695655".to_string(),
+            "695704".to_string(),
+            1693167107730,
+            1693167107730,
+            note_magic(),
+        )),
+        Note::Highlight(HighlightData(
+            "696152".to_string(),
+            "696191".to_string(),
+            1693167126701,
+            1693167126701,
+            note_magic(),
+        )),
+    ]
+}
+
+/// Full contents of `bookhl+note.azw3r`.
+pub fn bookhl_note_azw3r() -> ReaderDataFile {
+    let mut annotations = LinkedHashMap::new();
+    annotations.insert(
+        NoteType::Highlight,
+        IntervalTree(bookhl_note_azw3r_highlights()),
+    );
+    annotations.insert(
+        NoteType::Note,
+        IntervalTree(vec![Note::Note(AnnotationData(
+            "696187".to_string(),
+            "696191".to_string(),
+            1693167126654,
+            1693167126654,
+            note_magic(),
+            Some("Test note".to_string()),
+        ))]),
+    );
+    let fp = FontPreferences(
+        "_INVALID_,und:bookerly".to_string(),
+        0,
+        3,
+        1,
+        64,
+        158,
+        8,
+        158,
+        -1,
+        0,
+        "".to_string(),
+        -1,
+        "".to_string(),
+        false,
+        "".to_string(),
+        0,
+    );
+    let pages = [0].into_iter().chain((0..504).map(|n| n * 2300)).collect();
+    let apnx = APNXKey(
+        "1".to_string(),
+        "EBOK".to_string(),
+        true,
+        pages,
+        1,
+        504,
+        504,
+        "(1,a,1)".to_string(),
+    );
+    let mut rm = LinkedHashMap::new();
+
+    rm.insert("booklaunchedbefore".to_string(), "true".to_string());
+
+    ReaderDataFile {
+        font_preferences: Some(fp),
+        sync_lpr: Some(true),
+        nis_info_data: Some("".to_string()),
+        annotation_cache: Some(annotations),
+        apnx_key: Some(apnx),
+        language_store: None,
+        reader_metrics: Some(rm),
+    }
+}
+
 pub fn pdfannot_yjr() -> ReaderDataFile {
     let mut annotations = LinkedHashMap::new();
     let handwritten = handwritten_note_vec();
@@ -309,6 +524,46 @@ pub fn pdfannot_yjf() -> TimerDataFile {
     }
 }
 
+/// Value of an `apnx.key` field, laid out like the one in
+/// `bookhl+note.azw3r` (with a shorter page table): two strings, a
+/// bool, the page offsets and the rest of the page map.
+pub fn apnx_key_bytes() -> Vec<u8> {
+    let pages = [0, 2300, 4600, 6900];
+    let mut bytes = [
+        string_bytes("1"),
+        string_bytes("EBOK"),
+        vec![DataType::Boolean as u8, 1],
+        test_num(pages.len() as i32, DataType::Int),
+    ]
+    .concat();
+    for n in pages {
+        bytes.append(&mut test_num(n, DataType::Int));
+    }
+    bytes.append(&mut test_num(1, DataType::Int));
+    bytes.append(&mut test_num(504, DataType::Int));
+    bytes.append(&mut test_num(504, DataType::Int));
+    bytes.append(&mut string_bytes("(1,a,1)"));
+    bytes
+}
+
+/// Value of a `font.prefs` field, copied from `bookhl+note.azw3r`.
+/// The 16 fields are the typeface followed by 9 ints (line spacing,
+/// font size, alignment and margins as far as observed), then a string,
+/// an int, a string, a bool (bold), a string and a final int.
+pub fn font_prefs_bytes() -> Vec<u8> {
+    let mut bytes = string_bytes("_INVALID_,und:bookerly");
+    for n in [0, 3, 1, 64, 158, 8, 158, -1, 0] {
+        bytes.append(&mut test_num(n, DataType::Int));
+    }
+    bytes.append(&mut string_bytes(""));
+    bytes.append(&mut test_num(-1, DataType::Int));
+    bytes.append(&mut string_bytes(""));
+    bytes.extend([DataType::Boolean as u8, 0]);
+    bytes.append(&mut string_bytes(""));
+    bytes.append(&mut test_num(0, DataType::Int));
+    bytes
+}
+
 #[test]
 fn pdfannot_yjr_de_ser() {
     assert_eq!(
@@ -325,6 +580,125 @@ fn pdfannot_yjr_ser_de() {
     )
 }
 
+#[test]
+fn unit_struct_ser_de() {
+    let (_, data) = unit_struct();
+    assert_eq!(
+        from_bytes::<UnitStruct>(&to_bytes(&data).unwrap()).unwrap(),
+        data
+    )
+}
+
+#[test]
+fn struct_variant_ser_de() {
+    let (_, data) = struct_variant();
+    assert_eq!(
+        from_bytes::<StructVariantEnum>(&to_bytes(&data).unwrap()).unwrap(),
+        data
+    )
+}
+
+#[test]
+fn char_ser_de() {
+    for c in ['a', 'é', '字', '\u{fffc}'] {
+        assert_eq!(de_no_magic::<char>(&ser_no_magic(c)), c)
+    }
+}
+
+#[test]
+fn optional_tail_ser_de() {
+    let data = OptionalTailStruct {
+        field_1: OptionalTail(1, Some(simple_struct().1)),
+        field_2: NestedOptionalTail(2, Some(OptionalTail(3, Some(simple_struct().1)))),
+        field_3: OptionalTail(4, None),
+    };
+    assert_eq!(
+        from_bytes::<OptionalTailStruct>(&to_bytes(&data).unwrap()).unwrap(),
+        data
+    )
+}
+
+#[test]
+fn many_options_ser_de() {
+    let data = ManyOptionsStruct {
+        field_1: ManyOptions(1, None, None, None),
+        field_2: ManyOptions(2, Some(test_string().1), None, None),
+    };
+    assert_eq!(
+        from_bytes::<ManyOptionsStruct>(&to_bytes(&data).unwrap()).unwrap(),
+        data
+    )
+}
+
+#[test]
+fn empty_vec_vs_none_ser_de() {
+    for (field_1, field_2) in [
+        (Some(vec![]), Some(vec![])),
+        (None, None),
+        (Some(vec![]), None),
+        (None, Some(vec![])),
+    ] {
+        let data = HistoryStoreStruct {
+            field_1,
+            field_2,
+            field_3: 3,
+        };
+        assert_eq!(
+            from_bytes::<HistoryStoreStruct>(&to_bytes(&data).unwrap()).unwrap(),
+            data
+        )
+    }
+}
+
+#[test]
+fn extra_fields_de() {
+    let (bytes, data) = simple_struct();
+    let extra = de_no_magic::<ExtraFieldsStruct>(&bytes);
+    assert_eq!(extra.field_1, data.field_1);
+    assert_eq!(
+        extra.extra.get("field_2"),
+        Some(&KrdsValue::List(vec![KrdsValue::String(data.field_2)]))
+    );
+
+    let extra = from_bytes::<ExtraFieldsReaderData>(PDFANNOT_YJR).unwrap();
+    assert_eq!(
+        extra.extra.keys().collect::<Vec<_>>(),
+        ["annotation.cache.object", "language.store", "ReaderMetrics"]
+    );
+}
+
+#[test]
+fn annotation_types_ser_de_ser() {
+    let mut annotations = LinkedHashMap::new();
+    annotations.insert(NoteType::Handwritten, IntervalTree(handwritten_note_vec()));
+    annotations.insert(
+        NoteType::Bookmark,
+        IntervalTree(vec![Note::Bookmark(AnnotationData(
+            "AdgGAAAAAAAA:2586".to_string(),
+            "AdgGAAAAAAAA:2586".to_string(),
+            1693039707755,
+            1693039707755,
+            note_magic(),
+            None,
+        ))]),
+    );
+    let data = ReaderDataFile {
+        annotation_cache: Some(annotations),
+        ..pdfannot_yjr()
+    };
+    let bytes = to_bytes(&data).unwrap();
+    let de = from_bytes::<ReaderDataFile>(&bytes).unwrap();
+    assert_eq!(
+        de.annotation_cache
+            .as_ref()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        [&NoteType::Handwritten, &NoteType::Bookmark]
+    );
+    assert_eq!(to_bytes(&de).unwrap(), bytes);
+}
+
 #[test]
 fn pdfannot_yjf_de_ser() {
     assert_eq!(
@@ -341,6 +715,26 @@ fn bookhlnote_azw3r_de_ser() {
     )
 }
 
+#[test]
+fn bookhlnote_azw3r_de() {
+    assert_eq!(
+        from_bytes::<ReaderDataFile>(BOOK_HL_NOTE_AZW3R).unwrap(),
+        bookhl_note_azw3r()
+    )
+}
+
+#[test]
+fn bookhlnote_azw3r_highlights_de() {
+    let annotations = from_bytes::<ReaderDataFile>(BOOK_HL_NOTE_AZW3R)
+        .unwrap()
+        .annotation_cache
+        .unwrap();
+    assert_eq!(
+        annotations.get(&NoteType::Highlight),
+        Some(&IntervalTree(bookhl_note_azw3r_highlights()))
+    )
+}
+
 #[test]
 fn bookhlnote_azw3f_de_ser() {
     assert_eq!(
@@ -348,3 +742,318 @@ fn bookhlnote_azw3f_de_ser() {
         BOOK_HL_NOTE_AZW3F
     )
 }
+
+#[test]
+fn apnx_key_de_ser() {
+    let bytes = apnx_key_bytes();
+    assert_eq!(ser_no_magic(de_no_magic::<APNXKey>(&bytes)), bytes)
+}
+
+#[test]
+fn font_prefs_de_ser() {
+    let bytes = font_prefs_bytes();
+    assert_eq!(ser_no_magic(de_no_magic::<FontPreferences>(&bytes)), bytes)
+}
+
+#[test]
+fn font_prefs_in_file() {
+    let bytes = font_prefs_bytes();
+    assert!(BOOK_HL_NOTE_AZW3R
+        .windows(bytes.len())
+        .any(|window| window == bytes));
+}
+
+#[test]
+fn nested_interval_tree_ser_de() {
+    let tree = IntervalTree(vec![
+        IntervalTree(handwritten_note_vec()),
+        IntervalTree(vec![]),
+        IntervalTree(bookhl_note_azw3r_highlights()),
+    ]);
+    assert_eq!(
+        from_bytes::<IntervalTree<IntervalTree<Note>>>(&to_bytes(&tree).unwrap()).unwrap(),
+        tree
+    )
+}
+
+#[test]
+fn special_floats_ser_de() {
+    for value in [
+        f64::NAN,
+        -f64::NAN,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        -0.0,
+        f64::MIN_POSITIVE / 2.0,
+        f64::from_bits(1),
+    ] {
+        let mut data = pdfannot_yjf();
+        data.book_info_store = Some(BookInfoStore(0, value));
+        data.timer_model.as_mut().unwrap().3 = value;
+
+        let de = from_bytes::<TimerDataFile>(&to_bytes(&data).unwrap()).unwrap();
+        assert_eq!(de.book_info_store.unwrap().1.to_bits(), value.to_bits());
+        assert_eq!(de.timer_model.unwrap().3.to_bits(), value.to_bits());
+    }
+}
+
+#[test]
+fn special_floats_f32_ser_de() {
+    for value in [
+        f32::NAN,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        -0.0,
+        f32::from_bits(1),
+    ] {
+        assert_eq!(
+            de_no_magic::<f32>(&ser_no_magic(value)).to_bits(),
+            value.to_bits()
+        );
+    }
+}
+
+#[test]
+fn top_level_vec_ser_de() {
+    let (bytes, data) = test_vec_int();
+    let file = [crate::MAGIC as &[_], &bytes].concat();
+    assert_eq!(to_bytes(&data).unwrap(), file);
+    assert_eq!(from_bytes::<Vec<i32>>(&file).unwrap(), data);
+
+    let notes = handwritten_note_vec();
+    assert_eq!(
+        from_bytes::<Vec<Note>>(&to_bytes(&notes).unwrap()).unwrap(),
+        notes
+    );
+}
+
+#[test]
+fn top_level_scalar_ser_de() {
+    let file = [crate::MAGIC as &[_], &test_num(42_i64, DataType::Long)].concat();
+    assert_eq!(to_bytes(&42_i64).unwrap(), file);
+    assert_eq!(from_bytes::<i64>(&file).unwrap(), 42);
+    assert_eq!(
+        from_bytes::<String>(&to_bytes(&"en-US").unwrap()).unwrap(),
+        "en-US"
+    );
+}
+
+#[test]
+fn optional_tail_at_eof_ser_de() {
+    let data = OptionalTail(4, None);
+    let bytes = to_bytes(&data).unwrap();
+    assert_eq!(
+        bytes,
+        [crate::MAGIC as &[_], &test_num(4, DataType::Int)].concat()
+    );
+    assert_eq!(from_bytes::<OptionalTail>(&bytes).unwrap(), data);
+
+    // Values of a field run until its `FieldEnd`, which is still
+    // required at the end of the input.
+    let bytes = [
+        crate::MAGIC as &[_],
+        &[DataType::FieldBegin as u8, 0, 0, 1, b'a'],
+        &test_num(4, DataType::Int),
+    ]
+    .concat();
+    assert!(matches!(
+        from_bytes::<KrdsValue>(&bytes),
+        Err(crate::Error::Eof { pos }) if pos == bytes.len()
+    ));
+}
+
+#[test]
+fn char_keys_ser_de() {
+    let mut data = LinkedHashMap::new();
+    data.insert('a', 1);
+    data.insert('字', 2);
+    let bytes = [
+        test_num(2, DataType::Int),
+        vec![DataType::Char as u8, 0, b'a'],
+        test_num(1, DataType::Int),
+        vec![DataType::Char as u8, 0x5b, 0x57],
+        test_num(2, DataType::Int),
+    ]
+    .concat();
+    assert_eq!(ser_no_magic(&data), bytes);
+    assert_eq!(de_no_magic::<LinkedHashMap<char, i32>>(&bytes), data);
+
+    data.insert('😀', 3);
+    assert!(matches!(
+        to_bytes(&data),
+        Err(crate::Error::UnencodableChar('😀'))
+    ));
+}
+
+/// Empty strings are a marker byte without a length wherever they
+/// appear, with a `String` data type for values and without one for
+/// field names.
+#[test]
+fn empty_string_contexts_ser_de() {
+    let (empty, _) = empty_string();
+    let field = |name: &[u8], value: &[u8]| {
+        [
+            &[DataType::FieldBegin as u8] as &[_],
+            name,
+            value,
+            &[DataType::FieldEnd as u8],
+        ]
+        .concat()
+    };
+
+    let data = SimpleStruct {
+        field_1: 1,
+        field_2: String::new(),
+    };
+    let bytes = [
+        test_num(2, DataType::Int),
+        field(b"\0\0\x07field_1", &test_num(1, DataType::Int)),
+        field(b"\0\0\x07field_2", &empty),
+    ]
+    .concat();
+    assert_eq!(ser_no_magic(&data), bytes);
+    assert_eq!(de_no_magic::<SimpleStruct>(&bytes), data);
+
+    let (bytes, data) = test_vec_strings();
+    assert_eq!(ser_no_magic(&data), bytes);
+    assert_eq!(de_no_magic::<Vec<String>>(&bytes), data);
+
+    let data = LinkedHashMap::from_iter([(String::new(), 1)]);
+    let bytes = [
+        test_num(1, DataType::Int),
+        empty.clone(),
+        test_num(1, DataType::Int),
+    ]
+    .concat();
+    assert_eq!(ser_no_magic(&data), bytes);
+    assert_eq!(de_no_magic::<LinkedHashMap<String, i32>>(&bytes), data);
+
+    let data = ExtraFieldsStruct {
+        field_1: 1,
+        extra: LinkedHashMap::from_iter([(
+            String::new(),
+            KrdsValue::List(vec![KrdsValue::Int(2)]),
+        )]),
+    };
+    let bytes = [
+        test_num(2, DataType::Int),
+        field(b"\0\0\x07field_1", &test_num(1, DataType::Int)),
+        field(&[1], &test_num(2, DataType::Int)),
+    ]
+    .concat();
+    assert_eq!(de_no_magic::<ExtraFieldsStruct>(&bytes), data);
+}
+
+#[test]
+fn datatype_byte_width() {
+    for (bytes, dtype) in [
+        (ser_no_magic(true), DataType::Boolean),
+        (ser_no_magic(1u8), DataType::Byte),
+        (ser_no_magic(1i16), DataType::Short),
+        (ser_no_magic('a'), DataType::Char),
+        (ser_no_magic(1i32), DataType::Int),
+        (ser_no_magic(1f32), DataType::Float),
+        (ser_no_magic(1i64), DataType::Long),
+        (ser_no_magic(1f64), DataType::Double),
+    ] {
+        assert_eq!(bytes[0], dtype as u8);
+        assert_eq!(dtype.byte_width(), Some(bytes.len() - 1), "{:?}", dtype);
+    }
+    assert_eq!(DataType::String.byte_width(), None);
+    assert_eq!(DataType::FieldBegin.byte_width(), None);
+    assert_eq!(DataType::FieldEnd.byte_width(), None);
+}
+
+#[test]
+fn datatype_is_scalar() {
+    for value in (-2..=9).filter(|&value| value != 8) {
+        let dtype = DataType::try_from(value as i8).unwrap();
+        assert_eq!(dtype.is_scalar(), value >= 0, "{:?}", dtype);
+    }
+}
+
+#[test]
+fn fixed_array_ser_de() {
+    let array = [1, 2, 3];
+    let bytes = array.map(|n: i32| test_num(n, DataType::Int)).concat();
+    assert_eq!(ser_no_magic(array), bytes);
+    assert_eq!(de_no_magic::<[i32; 3]>(&bytes), array);
+
+    let arrays = vec![[1i64, 2], [3, 4]];
+    assert_eq!(
+        from_bytes::<Vec<[i64; 2]>>(&to_bytes(&arrays).unwrap()).unwrap(),
+        arrays
+    );
+}
+
+#[test]
+fn tuple_ser_de() {
+    let tuple = (7, "tuple".to_string(), true);
+    let bytes = [
+        test_num(7, DataType::Int),
+        string_bytes("tuple"),
+        vec![DataType::Boolean as u8, 1],
+    ]
+    .concat();
+    assert_eq!(ser_no_magic(tuple.clone()), bytes);
+    assert_eq!(de_no_magic::<(i32, String, bool)>(&bytes), tuple);
+
+    let optional_tail = (1, Some(2), None::<i32>);
+    assert_eq!(
+        from_bytes::<(i32, Option<i32>, Option<i32>)>(&to_bytes(&optional_tail).unwrap()).unwrap(),
+        optional_tail
+    );
+    assert!(matches!(
+        to_bytes(&(1, None::<i32>, 2)),
+        Err(crate::error::Error::OptionalNotAtEnd { field_index: 1 })
+    ));
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Key(i32);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Shape {
+    Point,
+    Circle(i32),
+    Rect { w: i32, h: i32 },
+}
+
+/// Keys starting with a `FieldBegin` are values like any other, only
+/// string keys may be the names of entries framed like struct fields.
+#[test]
+fn non_string_keys_ser_de() {
+    let keys = BTreeMap::from([(Key(5), 7), (Key(-1), 2)]);
+    assert_eq!(
+        from_bytes::<BTreeMap<Key, i32>>(&to_bytes(&keys).unwrap()).unwrap(),
+        keys
+    );
+
+    let shapes = BTreeMap::from([
+        (Shape::Point, "point".to_string()),
+        (Shape::Circle(3), "circle".to_string()),
+        (Shape::Rect { w: 1, h: 2 }, "rect".to_string()),
+    ]);
+    assert_eq!(
+        from_bytes::<BTreeMap<Shape, String>>(&to_bytes(&shapes).unwrap()).unwrap(),
+        shapes
+    );
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct WithVec {
+    v: Vec<Option<i32>>,
+}
+
+/// `None` elements are not written, so a sequence may be longer than
+/// what is left of the input.
+#[test]
+fn none_elements_ser_de() {
+    let data = WithVec {
+        v: vec![None, None, None],
+    };
+    assert_eq!(
+        from_bytes::<WithVec>(&to_bytes(&data).unwrap()).unwrap(),
+        data
+    );
+}
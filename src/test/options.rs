@@ -0,0 +1,101 @@
+//! Optionals in tuple structs: `None` is not written at all, so any
+//! arrangement the serializer accepts must round-trip and anything
+//! else must be rejected.
+
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::de::from_bytes;
+use crate::error::Error;
+use crate::ser::to_bytes;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Fields(i32, Option<i32>, Option<String>, Option<i64>);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Wrapped(Fields);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Outer(i32, Wrapped, Option<i32>);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Nested(i32, Option<Option<i32>>);
+
+/// Unlike [`Outer`] the inner tuple struct is not delimited, so its
+/// trailing `None`s run into the field after it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Inline(i32, Fields, Option<i32>);
+
+/// Index of the first `None` followed by a `Some`, if any.
+fn misplaced_none(fields: &[bool]) -> Option<usize> {
+    let first = fields.iter().position(|some| !some)?;
+    fields[first..].contains(&true).then_some(first)
+}
+
+fn fields() -> impl Strategy<Value = Fields> {
+    (
+        any::<i32>(),
+        any::<Option<i32>>(),
+        proptest::option::of("[a-z]{0,16}"),
+        any::<Option<i64>>(),
+    )
+        .prop_map(|(n, a, b, c)| Fields(n, a, b, c))
+}
+
+fn is_some(fields: &Fields) -> [bool; 4] {
+    [
+        true,
+        fields.1.is_some(),
+        fields.2.is_some(),
+        fields.3.is_some(),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn top_level(value in fields()) {
+        match (to_bytes(&value), misplaced_none(&is_some(&value))) {
+            (Ok(bytes), None) => prop_assert_eq!(from_bytes::<Fields>(&bytes).unwrap(), value),
+            (Err(Error::OptionalNotAtEnd { field_index }), Some(index)) => {
+                prop_assert_eq!(field_index, index)
+            }
+            (result, _) => prop_assert!(false, "unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn nested_in_field(n in any::<i32>(), inner in fields(), last in any::<Option<i32>>()) {
+        let value = Outer(n, Wrapped(inner), last);
+        match (to_bytes(&value), misplaced_none(&is_some(&value.1 .0))) {
+            (Ok(bytes), None) => prop_assert_eq!(from_bytes::<Outer>(&bytes).unwrap(), value),
+            (Err(Error::OptionalNotAtEnd { field_index }), Some(index)) => {
+                prop_assert_eq!(field_index, index)
+            }
+            (result, _) => prop_assert!(false, "unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn nested_inline(n in any::<i32>(), inner in fields(), last in any::<Option<i32>>()) {
+        let value = Inline(n, inner, last);
+        let expected = misplaced_none(&is_some(&value.1))
+            .or((value.1 .3.is_none() && last.is_some()).then_some(1));
+        match (to_bytes(&value), expected) {
+            (Ok(bytes), None) => prop_assert_eq!(from_bytes::<Inline>(&bytes).unwrap(), value),
+            (Err(Error::OptionalNotAtEnd { field_index }), Some(index)) => {
+                prop_assert_eq!(field_index, index)
+            }
+            (result, _) => prop_assert!(false, "unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn option_of_option(n in any::<i32>(), inner in any::<Option<Option<i32>>>()) {
+        let value = Nested(n, inner);
+        match to_bytes(&value) {
+            Ok(bytes) => prop_assert_eq!(from_bytes::<Nested>(&bytes).unwrap(), value),
+            Err(Error::NestedNone) => prop_assert_eq!(inner, Some(None)),
+            Err(e) => prop_assert!(false, "unexpected {:?}", e),
+        }
+    }
+}
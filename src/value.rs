@@ -0,0 +1,382 @@
+//! Schema-less representation of KRDS data.
+//!
+//! KRDS values do not describe their own structure, an `Int` may be a
+//! value or the length prefix of a sequence, map or struct. A
+//! [`KrdsValue`] tree therefore only records what is actually in the
+//! file: scalars and named objects (the values between a `FieldBegin`
+//! and its `FieldEnd`). All the values of a file are read with
+//! [`KrdsValue::from_document`], serializing them as a
+//! [`KrdsValue::List`] produces the exact bytes they were read from.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeTupleStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Name passed to `serialize_newtype_struct` for [`KrdsValue::Object`],
+/// the KRDS serializer takes the field name from the value instead.
+pub(crate) const OBJECT_TOKEN: &str = "$serde_krds::private::Object";
+
+/// Name passed to `serialize_newtype_struct` for the name of a
+/// [`KrdsValue::Object`], the KRDS serializer writes the inner string
+/// without a data type.
+pub(crate) const NAME_TOKEN: &str = "$serde_krds::private::Name";
+
+/// Name passed to `serialize_newtype_struct` for an [`IntVariant`],
+/// the KRDS serializer writes the variant index of the inner value.
+pub(crate) const INT_VARIANT_TOKEN: &str = "$serde_krds::private::IntVariant";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KrdsValue {
+    Bool(bool),
+    Int(i32),
+    Long(i64),
+    String(String),
+    Double(f64),
+    Short(i16),
+    Float(f32),
+    Byte(i8),
+    Char(char),
+    /// Values between a `FieldBegin` and its `FieldEnd`.
+    Object {
+        name: String,
+        fields: Vec<KrdsValue>,
+    },
+    /// All the values of a document.
+    List(Vec<KrdsValue>),
+}
+
+impl Serialize for KrdsValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            KrdsValue::Bool(v) => serializer.serialize_bool(*v),
+            KrdsValue::Int(v) => serializer.serialize_i32(*v),
+            KrdsValue::Long(v) => serializer.serialize_i64(*v),
+            KrdsValue::String(v) => serializer.serialize_str(v),
+            KrdsValue::Double(v) => serializer.serialize_f64(*v),
+            KrdsValue::Short(v) => serializer.serialize_i16(*v),
+            KrdsValue::Float(v) => serializer.serialize_f32(*v),
+            KrdsValue::Byte(v) => serializer.serialize_i8(*v),
+            KrdsValue::Char(v) => serializer.serialize_char(*v),
+            KrdsValue::Object { name, fields } => {
+                serializer.serialize_newtype_struct(OBJECT_TOKEN, &ObjectContents { name, fields })
+            }
+            KrdsValue::List(values) => {
+                let mut list = serializer.serialize_tuple_struct("KrdsValue", values.len())?;
+                for value in values {
+                    list.serialize_field(value)?;
+                }
+                list.end()
+            }
+        }
+    }
+}
+
+impl KrdsValue {
+    /// Read all the values of a KRDS file, in file order. Serializing
+    /// them as a [`KrdsValue::List`] produces the file again.
+    pub fn from_document(input: &[u8]) -> crate::Result<Vec<KrdsValue>> {
+        crate::from_document(input)
+    }
+}
+
+struct ObjectContents<'a> {
+    name: &'a str,
+    fields: &'a [KrdsValue],
+}
+
+impl<'a> Serialize for ObjectContents<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut object = serializer.serialize_tuple_struct("Object", self.fields.len() + 1)?;
+        object.serialize_field(&ObjectName(self.name))?;
+        for field in self.fields {
+            object.serialize_field(field)?;
+        }
+        object.end()
+    }
+}
+
+struct ObjectName<'a>(&'a str);
+
+impl<'a> Serialize for ObjectName<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(NAME_TOKEN, self.0)
+    }
+}
+
+struct KrdsValueVisitor;
+
+impl<'de> Visitor<'de> for KrdsValueVisitor {
+    type Value = KrdsValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any KRDS value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Bool(v))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Byte(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Short(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Long(v))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Float(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Double(v))
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<Self::Value, E> {
+        Ok(KrdsValue::Char(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(KrdsValue::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(KrdsValue::String(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(KrdsValue::List(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let (name, fields) = map
+            .next_entry()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        Ok(KrdsValue::Object { name, fields })
+    }
+}
+
+impl<'de> Deserialize<'de> for KrdsValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(KrdsValueVisitor)
+    }
+}
+
+/// Fieldless enum written as the `Int` index of its variant instead of
+/// the variant name, e.g. `IntVariant(Mode::Second)` is written as 1
+/// for `enum Mode { First, Second }`. The index is the position of the
+/// variant in the declaration, explicit discriminants (`Mode = 10`) are
+/// not known to serde and are ignored. Other serializers see the inner
+/// value unchanged.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IntVariant<T>(pub T);
+
+impl<T: Serialize> Serialize for IntVariant<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(INT_VARIANT_TOKEN, &self.0)
+    }
+}
+
+/// The KRDS deserializer reads enums from a variant index as well as a
+/// name, so nothing special is needed here.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for IntVariant<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(IntVariant)
+    }
+}
+
+/// A sequence of `Char` values, written as an `Int` length followed
+/// by one `Char` per element.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CharArray(pub Vec<char>);
+
+impl From<Vec<char>> for CharArray {
+    fn from(chars: Vec<char>) -> Self {
+        Self(chars)
+    }
+}
+
+impl From<&str> for CharArray {
+    fn from(s: &str) -> Self {
+        Self(s.chars().collect())
+    }
+}
+
+impl fmt::Display for CharArray {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|c| formatter.write_fmt(format_args!("{}", c)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test::*;
+    use crate::{from_bytes, from_bytes_partial, to_bytes, DataType, Error};
+
+    macro_rules! value_de_ser_test {
+        {$($file:expr => $name:ident),+} => {
+            $(#[test]
+            fn $name() {
+                let value = KrdsValue::List(KrdsValue::from_document($file).unwrap());
+                assert_eq!(&to_bytes(&value).unwrap(), $file)
+            })+
+        };
+    }
+
+    value_de_ser_test! {
+        PDFANNOT_YJR => pdfannot_yjr_value,
+        PDFANNOT_YJF => pdfannot_yjf_value,
+        BOOK_HL_NOTE_AZW3R => bookhlnote_azw3r_value,
+        BOOK_HL_NOTE_AZW3F => bookhlnote_azw3f_value
+    }
+
+    #[test]
+    fn document_values() {
+        for file in [PDFANNOT_YJR, BOOK_HL_NOTE_AZW3F] {
+            let values = KrdsValue::from_document(file).unwrap();
+            assert_eq!(&to_bytes(&KrdsValue::List(values)).unwrap(), file);
+        }
+        assert!(matches!(
+            KrdsValue::from_document(&PDFANNOT_YJR[..crate::MAGIC.len() + 3]),
+            Err(Error::UnexpectedEof { .. })
+        ));
+    }
+
+    #[test]
+    fn pdfannot_yjr_value_tree() {
+        let values = KrdsValue::from_document(PDFANNOT_YJR).unwrap();
+        assert_eq!(values[0], KrdsValue::Int(4));
+        assert_eq!(
+            from_bytes_partial::<KrdsValue>(PDFANNOT_YJR).unwrap().0,
+            values[0]
+        );
+        assert_eq!(
+            values[3],
+            KrdsValue::Object {
+                name: "language.store".to_string(),
+                fields: vec![KrdsValue::String("en-US".to_string()), KrdsValue::Int(4)]
+            }
+        );
+    }
+
+    #[test]
+    fn char_array() {
+        let chars = CharArray::from("aé€");
+        let bytes = to_bytes(&chars).unwrap();
+        assert_eq!(
+            &bytes[crate::MAGIC.len()..],
+            [
+                DataType::Int as u8,
+                0,
+                0,
+                0,
+                3,
+                DataType::Char as u8,
+                0,
+                b'a',
+                DataType::Char as u8,
+                0,
+                0xe9,
+                DataType::Char as u8,
+                0x20,
+                0xac,
+            ]
+        );
+        assert_eq!(from_bytes::<CharArray>(&bytes).unwrap(), chars);
+        assert_eq!(chars.to_string(), "aé€");
+    }
+
+    /// Values and field names use the same string encoding.
+    #[test]
+    fn string_contexts() {
+        let long = "b".repeat(300);
+        for (s, encoded) in [
+            ("", vec![1]),
+            ("a", vec![0, 0, 1, b'a']),
+            (long.as_str(), [&[0, 1, 44], long.as_bytes()].concat()),
+        ] {
+            let value = KrdsValue::Object {
+                name: s.to_string(),
+                fields: vec![KrdsValue::String(s.to_string())],
+            };
+            let bytes = [
+                &[DataType::FieldBegin as u8] as &[_],
+                &encoded,
+                &[DataType::String as u8],
+                &encoded,
+                &[DataType::FieldEnd as u8],
+            ]
+            .concat();
+            assert_eq!(ser_no_magic(&value), bytes);
+            assert_eq!(
+                from_bytes::<KrdsValue>(&to_bytes(&value).unwrap()).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    enum Mode {
+        First,
+        Second,
+        Third(i32),
+    }
+
+    #[test]
+    fn int_variant() {
+        let value = IntVariant(Mode::Second);
+        let bytes = ser_no_magic(value);
+        assert_eq!(bytes, test_num(1, DataType::Int));
+        assert_eq!(de_no_magic::<IntVariant<Mode>>(&bytes), value);
+        assert_eq!(
+            de_no_magic::<IntVariant<Mode>>(&test_num(0, DataType::Int)),
+            IntVariant(Mode::First)
+        );
+        assert!(to_bytes(&IntVariant(Mode::Third(1))).is_err());
+    }
+}